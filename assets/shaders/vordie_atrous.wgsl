@@ -0,0 +1,134 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+// One edge-avoiding à-trous pass: a 5x5 B3 spline kernel with its taps `params.offset` pixels
+// apart. Taps are weighted down by how far their luminance is from the centre, relative to the
+// noise the temporal pass estimated, and by how different the scene is under them. Taps in free
+// space are traced through the distance field, so light isn't blurred through a thin occluder.
+
+@group(0) @binding(0)
+var light_texture: texture_2d<f32>;
+
+// Luminance mean, mean of squares, variance and history length.
+@group(0) @binding(1)
+var moments_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var scene_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var distance_texture: texture_2d<f32>;
+
+struct VordieLightSettings {
+    u_rays_per_pixel: i32,
+    u_emission_multi: f32,
+    u_max_raymarch_steps: i32,
+    u_dist_mod: f32,
+    u_emission_range: f32,
+    u_emission_dropoff: f32,
+    u_ambient: vec3<f32>,
+    u_upsample_edge_sensitivity: f32,
+    u_noise_seed: u32,
+    u_denoise_iterations: u32,
+    u_denoise_edge_sensitivity: f32,
+}
+@group(0) @binding(4) var<uniform> settings: VordieLightSettings;
+
+struct Params {
+    screen_pixel_size: vec2<f32>,
+    offset: f32
+}
+@group(0) @binding(5) var<uniform> params: Params;
+
+// Luminance differences up to a few standard deviations of the noise still blur together.
+const LUMINANCE_PHI: f32 = 4.0;
+// Taps that take more steps than this to reach are left out, like the ones behind a wall.
+const MAX_TRACE_STEPS: i32 = 16;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn scene_at(pixel: vec2<i32>) -> vec4<f32> {
+    return textureLoad(scene_texture, pixel, 0);
+}
+
+// Distance to the nearest surface edge in pixels, negative inside the surfaces.
+fn distance_at(pixel: vec2<i32>) -> f32 {
+    let size = vec2<f32>(textureDimensions(distance_texture));
+    return textureLoad(distance_texture, pixel, 0).r / settings.u_dist_mod * max(size.x, size.y);
+}
+
+// Sphere traces from one free pixel to another, false when a surface is in the way.
+fn visible(start: vec2<i32>, end: vec2<i32>) -> bool {
+    let delta = vec2<f32>(end - start);
+    let length = length(delta);
+    var travelled: f32 = 0.0;
+    for (var i: i32 = 0; i < MAX_TRACE_STEPS; i += 1) {
+        if (travelled >= length) {
+            return true;
+        }
+        let edge_distance = distance_at(vec2<i32>(round(vec2<f32>(start) + delta * (travelled / length))));
+        if (edge_distance <= 0.0) {
+            return false;
+        }
+        // At least a pixel, the field only resolves whole pixels.
+        travelled += max(edge_distance, 1.0);
+    }
+    return travelled >= length;
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(light_texture));
+    let pixel = vec2<i32>(in.position.xy);
+    let step = i32(params.offset);
+
+    let center: vec4<f32> = textureLoad(light_texture, pixel, 0);
+    let center_luminance: f32 = luminance(center.rgb);
+    let center_scene: vec4<f32> = scene_at(pixel);
+    let center_surface: bool = center_scene.a > 0.0;
+
+    // The variance is blurred over 3x3 first, a single pixel's estimate is too noisy itself.
+    var variance: f32 = 0.0;
+    for (var y: i32 = -1; y <= 1; y += 1) {
+        for (var x: i32 = -1; x <= 1; x += 1) {
+            let coord = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let weight = f32((2 - abs(x)) * (2 - abs(y))) / 16.0;
+            variance += textureLoad(moments_texture, coord, 0).z * weight;
+        }
+    }
+    let luminance_scale: f32 = LUMINANCE_PHI * sqrt(variance) + 1e-4;
+
+    var kernel = array<f32, 3>(3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0);
+    var total: vec4<f32> = vec4<f32>(0.0);
+    var total_weight: f32 = 0.0;
+    for (var y: i32 = -2; y <= 2; y += 1) {
+        for (var x: i32 = -2; x <= 2; x += 1) {
+            let offset = vec2<i32>(x, y) * step;
+            let tap = pixel + offset;
+            if (any(tap < vec2<i32>(0)) || any(tap >= size)) {
+                continue;
+            }
+
+            let scene: vec4<f32> = scene_at(tap);
+            // Light on a surface doesn't mix with light in free space.
+            if ((scene.a > 0.0) != center_surface) {
+                continue;
+            }
+            // Nor across a wall, the two sides see different light.
+            if (!center_surface && !visible(pixel, tap)) {
+                continue;
+            }
+
+            let sample: vec4<f32> = textureLoad(light_texture, tap, 0);
+            let weight: f32 = kernel[abs(x)] * kernel[abs(y)]
+                * exp(-abs(luminance(sample.rgb) - center_luminance) / luminance_scale)
+                * exp(-settings.u_denoise_edge_sensitivity * distance(scene.rgb, center_scene.rgb));
+            total += sample * weight;
+            total_weight += weight;
+        }
+    }
+
+    // The centre always passes with a weight of at least (3/8)^2.
+    return total / total_weight;
+}
@@ -0,0 +1,40 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0)
+var scene_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+@group(0) @binding(2)
+var light_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var emitters_occluders_texture: texture_2d<f32>;
+
+struct VordieLightSettings {
+    u_rays_per_pixel: i32,
+    u_emission_multi: f32,
+    u_max_raymarch_steps: i32,
+    u_dist_mod: f32,
+    u_emission_range: f32,
+    u_emission_dropoff: f32,
+    u_ambient: vec3<f32>,
+}
+@group(0) @binding(4) var<uniform> settings: VordieLightSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let albedo: vec4<f32> = textureSample(scene_texture, texture_sampler, in.uv);
+    let light: vec3<f32> = textureSample(light_texture, texture_sampler, in.uv).rgb;
+
+    // The scene is lit by the GI irradiance plus the ambient term.
+    var col: vec3<f32> = albedo.rgb * (settings.u_ambient + light);
+
+    // Emitters are the light, they keep their own color.
+    let emitter: vec3<f32> = textureSample(emitters_occluders_texture, texture_sampler, in.uv).rgb;
+    if (max(emitter.r, max(emitter.g, emitter.b)) > 0.0) {
+        col = albedo.rgb;
+    }
+
+    return vec4<f32>(col, albedo.a);
+}
@@ -0,0 +1,32 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0)
+var debug_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let value: vec4<f32> = textureSample(debug_texture, texture_sampler, in.uv);
+
+#ifdef DEBUG_SCENE
+    // Emitters in their color, occluders are black with alpha set so they're shown in grey.
+    let is_emitter: bool = max(value.r, max(value.g, value.b)) > 0.0;
+    return vec4<f32>(select(vec3<f32>(0.5 * value.a), value.rgb, is_emitter), 1.0);
+#endif
+
+#ifdef DEBUG_UV
+    // Seed positions are already in 0..1.
+    return vec4<f32>(value.rg, 0.0, 1.0);
+#endif
+
+#ifdef DEBUG_DISTANCE_FIELD
+    // Outside the surfaces in grey, inside in red.
+    return vec4<f32>(select(vec3<f32>(value.r), vec3<f32>(-value.r, 0.0, 0.0), value.r < 0.0), 1.0);
+#endif
+
+#ifdef DEBUG_RADIANCE
+    // Unbounded radiance, squashed into 0..1.
+    return vec4<f32>(value.rgb / (1.0 + value.rgb), 1.0);
+#endif
+}
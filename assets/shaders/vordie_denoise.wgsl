@@ -1,5 +1,8 @@
-
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::view::View
+
+// Temporal half of the denoiser: accumulates the GI in the reprojected history and estimates
+// how noisy each pixel is. The variance steers the à-trous passes in `vordie_atrous.wgsl`.
 
 @group(0) @binding(0)
 var screen_texture: texture_2d<f32>;
@@ -10,11 +13,17 @@ var texture_sampler: sampler;
 var history_texture: texture_2d<f32>;
 
 struct VordieLightSettings {
-    u_dis_mod: f32,
     u_rays_per_pixel: i32,
     u_emission_multi: f32,
     u_max_raymarch_steps: i32,
     u_dist_mod: f32,
+    u_emission_range: f32,
+    u_emission_dropoff: f32,
+    u_ambient: vec3<f32>,
+    u_upsample_edge_sensitivity: f32,
+    u_noise_seed: u32,
+    u_denoise_iterations: u32,
+    u_denoise_edge_sensitivity: f32,
 }
 @group(0) @binding(3) var<uniform> settings: VordieLightSettings;
 
@@ -24,81 +33,86 @@ struct Params {
 }
 @group(0) @binding(4) var<uniform> params: Params;
 
+// Luminance mean, mean of squares, variance and history length.
+@group(0) @binding(5)
+var moments_texture: texture_2d<f32>;
+
+@group(0) @binding(6)
+var<uniform> view: View;
+
+struct PreviousView {
+    clip_from_world: mat4x4<f32>,
+}
+@group(0) @binding(7)
+var<uniform> previous_view: PreviousView;
+
+// Past this many frames the history stops gaining weight, so it still follows moving lights. Kept
+// in step with the `u_denoise_iterations` docs.
+const MAX_HISTORY_LENGTH: f32 = 5.0;
+// Fewer frames than this give no usable temporal variance, the neighbours stand in for them.
+const MIN_TEMPORAL_VARIANCE_LENGTH: f32 = 4.0;
 
 struct Output {
-    @location(0) view_target: vec4<f32>,
+    @location(0) light: vec4<f32>,
     @location(1) history: vec4<f32>,
+    @location(2) moments: vec4<f32>,
 };
 
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
 
-fn lin_to_srgb(color: vec4<f32>) -> vec4<f32> {
-    let x: vec3<f32> = color.rgb * 12.92;
-    let y: vec3<f32> = 1.055 * pow(clamp(color.rgb, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)), vec3<f32>(0.4166667, 0.4166667, 0.4166667)) - 0.055;
-    var clr: vec3<f32> = color.rgb;
-    clr.r = select(y.r, x.r, color.r < 0.0031308);
-    clr.g = select(y.g, x.g, color.g < 0.0031308);
-    clr.b = select(y.b, x.b, color.b < 0.0031308);
-    return vec4<f32>(clr, color.a);
+// Where the pixel was on screen last frame, in uv.
+fn reproject(uv: vec2<f32>) -> vec2<f32> {
+    let world = view.world_from_clip * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    let clip = previous_view.clip_from_world * vec4<f32>(world.xy / world.w, 0.0, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
 }
 
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> Output {
-  // Very basic denoising algorithm.
-
-  // If pixel color brighter than this, don't denoise.
-  // let denoise_threshold: f32 = 0.9;
-  // if (textureSample(screen_texture, texture_sampler, in.uv).x > denoise_threshold) {
-  //   return textureSample(screen_texture, texture_sampler, in.uv);
-  // }
-
-  // How many 3x3s of pixels to sample.
-  let denoise_count: f32 = 1.0;
-
-  var mixed_color: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 1.0);
-
-  // Sample surrounding pixels
-  // for(var i: f32 = 0.0; i < denoise_count; i = i + 1.0) {
-  //   for(var x: f32 = -1.0 - i; x <= 1.0 + i; x += 1.0) {
-  //     for(var y: f32 = -1.0 - i; y <= 1.0 + i; y += 1.0) {
-  //       let voffset: vec2<f32> = in.uv + (vec2<f32>(x, y) * 1.0 / params.screen_pixel_size);
-      
-  //       let pixel_color: vec4<f32> = textureSample(screen_texture, texture_sampler, voffset);
-
-  //       mixed_color += pixel_color;
-  //     }
-  //   }
-  //   mixed_color = mixed_color / f32(9); // 3x3
-  // }
-
-  for(var i: f32 = 0.0; i < denoise_count; i = i + 1.0) {
-      for(var x: f32 = -1.0 - i; x <= 1.0 + i; x += 1.0) {
-          for(var y: f32 = -1.0 - i; y <= 1.0 + i; y += 1.0) {
-              // var st: vec2<f32> = in.uv;
-              // st.x = st.x * inv_aspect;
-              let voffset = in.uv + vec2<f32>(x, y) * 1.0 / params.screen_pixel_size;
-              let pixel_color: vec4<f32> = textureSample(screen_texture, texture_sampler, voffset);
-
-              mixed_color += pixel_color;
-          }
-      }
-      mixed_color /= 9.0;
-  }
-
-  let col: vec4<f32> = textureSample(screen_texture, texture_sampler, in.uv);
-  let integ: f32 = 2.0;
-  mixed_color = vec4<f32>((1.0 - (1.0 / integ)) * mixed_color.rgb + col.rgb * (1.0 / integ), col.a);
-
-  // Make it less blurry by increasing the weight of the original pixel accounting for brightness (brighter pixels are less denoised).
-  // mixed_color = mixed_color * 0.8 + col * 0.2;
-
-  mixed_color /= f32(denoise_count);
-
-  var out: Output;
-  out.view_target = lin_to_srgb(mixed_color);
-  out.history = mixed_color;
-
-  // out.view_target = lin_to_srgb(textureSample(screen_texture, texture_sampler, in.uv));
-  // out.history = textureSample(screen_texture, texture_sampler, in.uv);
-
-  return out;
-}
\ No newline at end of file
+    let size = vec2<i32>(textureDimensions(screen_texture));
+    let pixel = vec2<i32>(in.position.xy);
+    let current: vec4<f32> = textureLoad(screen_texture, pixel, 0);
+    let current_luminance: f32 = luminance(current.rgb);
+
+    let history_pixel = vec2<i32>(floor(reproject(in.uv) * vec2<f32>(size)));
+    // Off screen last frame, the area was just revealed and has no history.
+    let revealed: bool = any(history_pixel < vec2<i32>(0)) || any(history_pixel >= size);
+    let history_coord = clamp(history_pixel, vec2<i32>(0), size - 1);
+    let history: vec4<f32> = textureLoad(history_texture, history_coord, 0);
+    let history_moments: vec4<f32> = textureLoad(moments_texture, history_coord, 0);
+    // A cleared history has a length of 0 as well.
+    let history_length: f32 = select(history_moments.w, 0.0, revealed);
+
+    let length: f32 = min(history_length + 1.0, MAX_HISTORY_LENGTH);
+    let alpha: f32 = 1.0 / length;
+    let color: vec4<f32> = mix(history, current, alpha);
+    let moments: vec2<f32> = mix(
+        history_moments.xy,
+        vec2<f32>(current_luminance, current_luminance * current_luminance),
+        alpha,
+    );
+    var variance: f32 = max(moments.y - moments.x * moments.x, 0.0);
+
+    if (length < MIN_TEMPORAL_VARIANCE_LENGTH) {
+        var spatial_moments: vec2<f32> = vec2<f32>(0.0);
+        for (var y: i32 = -1; y <= 1; y += 1) {
+            for (var x: i32 = -1; x <= 1; x += 1) {
+                let coord = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+                let l: f32 = luminance(textureLoad(screen_texture, coord, 0).rgb);
+                spatial_moments += vec2<f32>(l, l * l);
+            }
+        }
+        spatial_moments /= 9.0;
+        variance = max(spatial_moments.y - spatial_moments.x * spatial_moments.x, 0.0);
+    }
+
+    var out: Output;
+    // Kept linear, the composite pass multiplies it onto the scene.
+    out.light = color;
+    out.history = color;
+    out.moments = vec4<f32>(moments, variance, length);
+    return out;
+}
@@ -1,5 +1,6 @@
 
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import "shaders/vordie_jfa_functions.wgsl"::jfa_signed_distance
 
 @group(0) @binding(0)
 var screen_texture: texture_2d<f32>;
@@ -13,14 +14,17 @@ struct VordieLightSettings {
     u_dist_mod: f32,
 }
 @group(0) @binding(2) var<uniform> settings: VordieLightSettings;
+@group(0) @binding(3)
+var inverse_voronoi_texture: texture_2d<f32>;
 
 
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    let in_diffuse = textureSample(screen_texture, texture_sampler, in.uv);
-
-    let dist: f32 = distance(in_diffuse.xy, in.uv);
-    let mapped: f32 = clamp(dist * settings.u_dist_mod, 0.0, 1.0);
-    
-    return vec4<f32>(vec3<f32>(mapped), 1.0);
-}
\ No newline at end of file
+    return jfa_signed_distance(
+        screen_texture,
+        inverse_voronoi_texture,
+        texture_sampler,
+        in.uv,
+        settings.u_dist_mod,
+    );
+}
@@ -1,23 +1,106 @@
 
-#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::view::View
 
 @group(0) @binding(0)
-var screen_texture: texture_2d<f32>;
-@group(0) @binding(1)
-var texture_sampler: sampler;
+var<uniform> view: View;
 
+struct VordieSceneInstance {
+    world_from_local: mat4x4<f32>,
+    color: vec4<f32>,
+    uv_offset_scale: vec4<f32>,
+    transmittance: vec4<f32>,
+    flags: u32,
+    emitter_id: u32,
+}
+@group(1) @binding(0)
+var<uniform> instance: VordieSceneInstance;
 
-@fragment
-fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    // TODO: White = emiter, black = occluder
+@group(2) @binding(0)
+var base_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var base_sampler: sampler;
+
+const FLAG_EMITTER: u32 = 1u;
+const FLAG_DISC: u32 = 2u;
 
-    let in_diffuse   = textureSample(screen_texture, texture_sampler, in.uv);
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
 
-    return vec4<f32>(
-        in_diffuse.r,
-        in_diffuse.g,
-        in_diffuse.b,
-        in_diffuse.a
+#ifdef QUAD
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Unit quad from (0, 0) to (1, 1), the instance transform scales and anchors it.
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
     );
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    out.position = view.clip_from_world * instance.world_from_local * vec4<f32>(corner, 0.0, 1.0);
+    out.uv = instance.uv_offset_scale.xy + corner * instance.uv_offset_scale.zw;
+    return out;
+}
+#else
+struct Vertex {
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_UVS
+    @location(1) uv: vec2<f32>,
+#endif
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = view.clip_from_world * instance.world_from_local * vec4<f32>(vertex.position, 1.0);
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#else
+    out.uv = vec2<f32>(0.0);
+#endif
+    return out;
+}
+#endif
+
+struct FragmentOutput {
+    @location(0) emitters_occluders: vec4<f32>,
+    @location(1) transmittance: vec4<f32>,
+    @location(2) emitter_id: u32,
+    @location(3) albedo: vec4<f32>,
+};
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    // Discs are cut out of the quad, point lights have no texture.
+    if ((instance.flags & FLAG_DISC) != 0u && length(in.uv * 2.0 - 1.0) > 1.0) {
+        discard;
+    }
+
+    // Emitters are written with their color, occluders are written black.
+    // Alpha marks the pixel as a surface for the jump flood seed.
+    let in_diffuse = textureSample(base_texture, base_sampler, in.uv) * instance.color;
+    if (in_diffuse.a < 0.5) {
+        discard;
+    }
 
-}
\ No newline at end of file
+    // Emitters block every ray that reaches them, occluders let through their transmittance.
+    var out: FragmentOutput;
+    out.emitter_id = instance.emitter_id;
+    // Only occluders bounce light, in their own color. An emitter's color is its light.
+    if ((instance.flags & FLAG_EMITTER) != 0u) {
+        out.emitters_occluders = vec4<f32>(in_diffuse.rgb, 1.0);
+        out.transmittance = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        out.albedo = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    } else {
+        out.emitters_occluders = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        out.transmittance = vec4<f32>(instance.transmittance.rgb, 1.0);
+        out.albedo = vec4<f32>(in_diffuse.rgb, 1.0);
+    }
+    return out;
+}
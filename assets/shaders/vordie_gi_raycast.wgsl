@@ -1,5 +1,6 @@
 
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::view::View
 
 @group(0) @binding(0)
 var u_distance_data: texture_2d<f32>;
@@ -13,6 +14,14 @@ struct VordieLightSettings {
     u_dist_mod: f32,
     u_emission_range: f32,
     u_emission_dropoff: f32,
+    u_ambient: vec3<f32>,
+    u_upsample_edge_sensitivity: f32,
+    u_noise_seed: u32,
+    u_denoise_iterations: u32,
+    u_denoise_edge_sensitivity: f32,
+    u_bounce_intensity: f32,
+    u_max_bounces: u32,
+    u_light_penetration: f32,
 }
 @group(0) @binding(2) 
 var<uniform> settings: VordieLightSettings;
@@ -20,16 +29,160 @@ var<uniform> settings: VordieLightSettings;
 @group(0) @binding(3)
 var u_scene_data: texture_2d<f32>;
 
+// Light around the surfaces after the last bounce.
 @group(0) @binding(4)
-var history_texture: texture_2d<f32>;
+var surface_radiance_texture: texture_2d<f32>;
 
+// Sampling is a `VordieRaySampling`: 0 white noise, 1 stratified, 2 R2, 3 blue noise.
+struct Noise {
+    frame: u32,
+    sampling: u32,
+}
 @group(0) @binding(5)
-var<uniform> time: f32;
+var<uniform> noise: Noise;
+
+@group(0) @binding(6)
+var<uniform> view: View;
+
+struct SpotLight {
+    color: vec4<f32>,
+    position: vec2<f32>,
+    direction: vec2<f32>,
+    cos_inner: f32,
+    cos_outer: f32,
+    // Negative range or dropoff means the camera's settings are used.
+    range: f32,
+    dropoff: f32,
+}
+struct SpotLights {
+    count: u32,
+    data: array<SpotLight>,
+}
+@group(0) @binding(7)
+var<storage, read> spot_lights: SpotLights;
+
+@group(0) @binding(8)
+var u_scene_transmittance: texture_2d<f32>;
+
+@group(0) @binding(9)
+var u_emitter_ids: texture_2d<u32>;
+
+// Negative range or dropoff means the camera's settings are used.
+struct Emitter {
+    intensity: f32,
+    range: f32,
+    dropoff: f32,
+}
+struct Emitters {
+    data: array<Emitter>,
+}
+@group(0) @binding(10)
+var<storage, read> emitters: Emitters;
+
+@group(0) @binding(11)
+var u_albedo_data: texture_2d<f32>;
+
+@group(0) @binding(12)
+var blue_noise_texture: texture_2d<f32>;
+
+// The next cascade up while the cascades are merged, cascade 0 once the GI is gathered.
+@group(0) @binding(13)
+var cascade_texture: texture_2d<f32>;
+
+// Bounce drawn, 0 is the direct light. With radiance cascades also the cascade drawn. Mode is a
+// `VordieGiMode`.
+struct GiPass {
+    cascade: u32,
+    cascade_count: u32,
+    bounce: u32,
+    mode: u32,
+}
+@group(0) @binding(14)
+var<uniform> gi_pass: GiPass;
+
+@group(0) @binding(15)
+var sky_texture: texture_2d<f32>;
+
+// Mode 0 is no sky, 1 the gradient from `down` to `up` and 2 the texture.
+struct Sky {
+    mode: u32,
+    up: vec4<f32>,
+    down: vec4<f32>,
+}
+@group(0) @binding(16)
+var<uniform> sky: Sky;
+
+// `mode` of the radiance cascades, 0 casts random rays.
+const GI_MODE_RADIANCE_CASCADES: u32 = 1u;
+// Length in pixels of cascade 0's interval, each cascade up is 4 times longer.
+const CASCADE_INTERVAL: f32 = 2.0;
+// How far in pixels past the edge an occluder pixel takes its light from, clear of the pixels
+// and cascade 0 probes whose rays stop at the edge straight away.
+const OCCLUDER_LIGHT_OFFSET: f32 = 2.0;
 
 const PI: f32 = 3.141596;
 
-fn random(st: vec2<f32>) -> f32 {
-    return fract(sin(dot(st.xy, vec2<f32>(12.9898, 78.233))) * 43758.5453123);
+// PCG hash, from "Hash Functions for GPU Rendering" (Jarzynski and Olano).
+fn pcg_hash(value: u32) -> u32 {
+    let state: u32 = value * 747796405u + 2891336453u;
+    let word: u32 = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Random hash of a pixel, the same for the same pixel, frame and noise seed.
+fn pixel_hash(pixel: vec2<u32>) -> u32 {
+    let frame_seed: u32 = pcg_hash(noise.frame + pcg_hash(settings.u_noise_seed));
+    return pcg_hash(pixel.x + pcg_hash(pixel.y + frame_seed));
+}
+
+// Maps a 32 bit fixed point fraction to 0..1.
+fn unit_float(fraction: u32) -> f32 {
+    return f32(fraction >> 8u) / 16777216.0;
+}
+
+// Offsets the R2 and blue noise patterns, so each noise seed gets its own.
+fn seed_offset() -> vec2<u32> {
+    let hash: u32 = pcg_hash(settings.u_noise_seed);
+    return vec2<u32>(hash & 0xffffu, hash >> 16u);
+}
+
+// Rotation in 0..1 of the rays of a pixel. The R2 and blue noise patterns are steady per pixel and
+// shifted by the golden ratio every frame, so the history sees each rotation once before repeats.
+fn ray_rotation(pixel: vec2<u32>) -> f32 {
+    // The golden ratio and the R2 sequence's constants in 32 bit fixed point, so they wrap exactly.
+    let frame_shift: u32 = noise.frame * 2654435769u;
+    switch noise.sampling {
+        case 2u: {
+            let p: vec2<u32> = pixel + seed_offset();
+            return unit_float(p.x * 3242174889u + p.y * 2447445414u + frame_shift);
+        }
+        case 3u: {
+            let size: vec2<u32> = textureDimensions(blue_noise_texture, 0);
+            let blue_noise: f32 = textureLoad(blue_noise_texture, (pixel + seed_offset()) % size, 0).r;
+            return fract(blue_noise + unit_float(frame_shift));
+        }
+        default: {
+            return unit_float(pixel_hash(pixel));
+        }
+    }
+}
+
+// Angle of the `ray`th ray of a pixel, in radians.
+fn ray_angle(pixel: vec2<u32>, rotation: f32, ray: i32) -> f32 {
+    let golden_angle: f32 = PI * 0.7639320225; // Magic number for good ray distribution.
+    let rays: f32 = f32(settings.u_rays_per_pixel);
+    switch noise.sampling {
+        case 0u: {
+            return rotation * 2.0 * PI + golden_angle * f32(ray);
+        }
+        case 1u: {
+            let jitter: f32 = unit_float(pcg_hash(pixel_hash(pixel) + u32(ray)));
+            return (f32(ray) + jitter) / rays * 2.0 * PI;
+        }
+        default: {
+            return (rotation + f32(ray) / rays) * 2.0 * PI;
+        }
+    }
 }
 
 fn dist_tonemap(col: vec3<f32>, dist: f32) -> vec3<f32> {
@@ -40,16 +193,26 @@ fn dist_tonemap(col: vec3<f32>, dist: f32) -> vec3<f32> {
 struct SurfaceResult {
     emissive: f32,
     colour: vec3<f32>,
+    range: f32,
+    dropoff: f32,
 }
 fn get_surface(uv: vec2<f32>, ray_origin: vec2<f32>) -> SurfaceResult {
     let screen_pixel_size: vec2<f32> = vec2<f32>(textureDimensions(u_scene_data, 0).xy);
     let emissive_data = textureSample(u_scene_data, texture_sampler, uv);
 
     let color_by_dist = dist_tonemap(emissive_data.rgb, distance(ray_origin*screen_pixel_size, uv*screen_pixel_size));
-    
+
+    // Falloff of the emitter that was hit.
+    let ids_size: vec2<u32> = textureDimensions(u_emitter_ids, 0).xy;
+    let id_coord: vec2<u32> = min(vec2<u32>(uv * vec2<f32>(ids_size)), ids_size - 1u);
+    let emitter_id: u32 = min(textureLoad(u_emitter_ids, id_coord, 0).r, arrayLength(&emitters.data) - 1u);
+    let emitter: Emitter = emitters.data[emitter_id];
+
     return SurfaceResult(
-      max(emissive_data.r, max(emissive_data.g, emissive_data.b)) * settings.u_emission_multi,
-      emissive_data.rgb
+      max(emissive_data.r, max(emissive_data.g, emissive_data.b)) * settings.u_emission_multi * emitter.intensity,
+      emissive_data.rgb,
+      select(settings.u_emission_range, emitter.range, emitter.range >= 0.0),
+      select(settings.u_emission_dropoff, emitter.dropoff, emitter.dropoff >= 0.0),
     );
 }
 
@@ -57,34 +220,77 @@ struct RaymarchResult {
     hit: bool,
     hit_pos: vec2<f32>,
     ray_dist: f32,
+    // Light let through by the translucent occluders the ray passed.
+    transmittance: vec3<f32>,
+    // Left the view without hitting anything, it sees the sky.
+    escaped: bool,
+}
+// `dir` and the returned distance are in aspect-corrected UV, the same space as the distance field.
+fn raymarch(origin: vec2<f32>, dir: vec2<f32>, reso: vec2<f32>) -> RaymarchResult {
+    return raymarch_interval(origin, dir, reso, 0.0, 2.0);
 }
-fn raymarch(origin: vec2<f32>, dir: vec2<f32>, time: f32, reso: vec2<f32>) -> RaymarchResult {
-    var current_dist: f32 = 0.0;
+
+// Only marches the part of the ray between `start` and `end`, the returned distance is still from
+// `origin`.
+fn raymarch_interval(origin: vec2<f32>, dir: vec2<f32>, reso: vec2<f32>, start: f32, end: f32) -> RaymarchResult {
+    var current_dist: f32 = start;
+    var transmittance: vec3<f32> = vec3<f32>(1.0);
+
+    // Steps are taken in UV, which stretches the longer side.
+    let uv_dir: vec2<f32> = dir / aspect(reso);
+
+    // Translucent occluders are crossed one texel at a time.
+    let texel: f32 = 1.0 / max(reso.x, reso.y);
+    let texel_world: f32 = distance(uv_to_world(uv_dir * texel), uv_to_world(vec2<f32>(0.0)));
+
     for (var i: i32 = 0; i < settings.u_max_raymarch_steps; i = i + 1) {
-        var sample_point: vec2<f32> = origin + dir * current_dist;
+        var sample_point: vec2<f32> = origin + uv_dir * current_dist;
         
-        // early exit if we hit the edge of the screen.
-        if (sample_point.x > 1.0 || sample_point.x < 0.0 || sample_point.y > 1.0 || sample_point.y < 0.0) {
+        // early exit if we hit the edge of the screen or the end of the interval.
+        let off_screen: bool = sample_point.x > 1.0 || sample_point.x < 0.0 || sample_point.y > 1.0 || sample_point.y < 0.0;
+        if (off_screen || current_dist >= end) {
             return RaymarchResult(
                 false,
                 vec2<f32>(0.0),
                 0.0,
+                transmittance,
+                off_screen,
             );
         }
 
         var dist_to_surface: f32 = textureSample(u_distance_data, texture_sampler, sample_point).r / settings.u_dist_mod;
 
-        // we've hit a surface if distance field returns 0 or close to 0 (due to our distance field using a 16-bit float
-        // the precision isn't enough to just check against 0).
-        if (dist_to_surface < 0.5 / max(reso.x, reso.y)) {
-            return RaymarchResult(
-                true,
-                sample_point,
-                current_dist,
-            );
-        }
+        // we've hit a surface if distance field returns 0 or less inside a surface, or close to 0 (due to our distance
+        // field using a 16-bit float the precision isn't enough to just check against 0).
+        if (dist_to_surface < 0.5 * texel) {
+            let surface_transmittance: vec3<f32> = textureSample(u_scene_transmittance, texture_sampler, sample_point).rgb;
+
+            // Opaque occluders and emitters stop the ray.
+            if (max(surface_transmittance.r, max(surface_transmittance.g, surface_transmittance.b)) <= 0.0) {
+                return RaymarchResult(
+                    true,
+                    sample_point,
+                    current_dist,
+                    transmittance,
+                    false,
+                );
+            }
 
+            // Inside a translucent occluder, attenuate by the distance travelled (Beer-Lambert).
+            transmittance *= pow(surface_transmittance, vec3<f32>(texel_world));
+            if (max(transmittance.r, max(transmittance.g, transmittance.b)) < 0.001) {
+                return RaymarchResult(
+                    false,
+                    vec2<f32>(0.0),
+                    0.0,
+                    vec3<f32>(0.0),
+                    false,
+                );
+            }
 
+            current_dist = current_dist + texel;
+            continue;
+        }
 
         // if we don't hit a surface, continue marching along the ray.
         current_dist = current_dist + dist_to_surface;
@@ -94,27 +300,279 @@ fn raymarch(origin: vec2<f32>, dir: vec2<f32>, time: f32, reso: vec2<f32>) -> Ra
         false,
         vec2<f32>(0.0),
         0.0,
+        transmittance,
+        false,
+    );
+}
+
+// Scales UV so both axes use the unit of the longer side.
+fn aspect(reso: vec2<f32>) -> vec2<f32> {
+    return reso / max(reso.x, reso.y);
+}
+
+fn uv_to_world(uv: vec2<f32>) -> vec2<f32> {
+    let world = view.world_from_clip * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return world.xy / world.w;
+}
+
+fn world_to_uv(world: vec2<f32>) -> vec2<f32> {
+    let clip = view.clip_from_world * vec4<f32>(world, 0.0, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+}
+
+// Direct light from the spot lights, with a shadow ray marched towards each light.
+fn get_spot_lights(uv: vec2<f32>, reso: vec2<f32>) -> vec3<f32> {
+    let world_pos: vec2<f32> = uv_to_world(uv);
+
+    var col: vec3<f32> = vec3<f32>(0.0);
+    for (var i: u32 = 0u; i < spot_lights.count; i = i + 1u) {
+        let light: SpotLight = spot_lights.data[i];
+
+        let to_light: vec2<f32> = light.position - world_pos;
+        let light_dist: f32 = length(to_light);
+        let light_uv: vec2<f32> = world_to_uv(light.position);
+        let to_light_uv: vec2<f32> = (light_uv - uv) * aspect(reso);
+        let uv_dist: f32 = length(to_light_uv);
+
+        // The light's own range is in world units, the camera's is on screen like an emitter's.
+        let own_range: bool = light.range >= 0.0;
+        let range: f32 = select(settings.u_emission_range, light.range, own_range);
+        let dist: f32 = select(uv_dist, light_dist, own_range);
+        let dropoff: f32 = select(settings.u_emission_dropoff, light.dropoff, light.dropoff >= 0.0);
+        if (dist > range || light_dist <= 0.0) {
+            continue;
+        }
+
+        // Angular falloff between the inner and outer cone.
+        let cos_angle: f32 = dot(-to_light / light_dist, light.direction);
+        let cone: f32 = clamp((cos_angle - light.cos_outer) / max(light.cos_inner - light.cos_outer, 0.0001), 0.0, 1.0);
+        if (cone <= 0.0) {
+            continue;
+        }
+
+        // Shadow ray, anything hit before reaching the light blocks it.
+        let ray_res: RaymarchResult = raymarch(uv, to_light_uv / uv_dist, reso);
+        if (ray_res.hit && ray_res.ray_dist < uv_dist - 1.0 / max(reso.x, reso.y)) {
+            continue;
+        }
+
+        let dist_att: f32 = pow(max(1.0 - (dist * dist) / (range * range), 0.0), dropoff);
+        col += light.color.rgb * cone * dist_att * ray_res.transmittance;
+    }
+
+    return col;
+}
+
+// Light the occluder hit at `uv` bounces back, in its albedo. The hit is on the edge between
+// the lit free space and the dark inside, so the brightest of the 3x3 pixels around it is taken.
+fn get_bounce_light(uv: vec2<f32>, reso: vec2<f32>) -> vec4<f32> {
+    var light: vec4<f32> = vec4<f32>(0.0);
+    for(var x: f32 = -1.0; x <= 1.0; x += 1.0) {
+        for(var y: f32 = -1.0; y <= 1.0; y += 1.0) {
+            let pixel = textureSample(surface_radiance_texture, texture_sampler, uv + vec2<f32>(x, y) / reso);
+            if(pixel.a > light.a) {
+                light = pixel;
+            }
+        }
+    }
+
+    let albedo: vec3<f32> = textureSample(u_albedo_data, texture_sampler, uv).rgb;
+    return vec4<f32>(light.rgb * albedo, light.a * max(albedo.r, max(albedo.g, albedo.b))) * settings.u_bounce_intensity;
+}
+
+// Sky seen by a ray going in `dir`, in aspect-corrected UV with y down the screen.
+fn sky_radiance(dir: vec2<f32>) -> vec3<f32> {
+    switch sky.mode {
+        case 1u: {
+            return mix(sky.down.rgb, sky.up.rgb, 0.5 - dir.y * 0.5);
+        }
+        case 2u: {
+            let width: u32 = textureDimensions(sky_texture, 0).x;
+            // Counter-clockwise on screen, so up the screen is a quarter turn.
+            let turn: f32 = fract(atan2(-dir.y, dir.x) / (2.0 * PI));
+            return textureLoad(sky_texture, vec2<u32>(min(u32(turn * f32(width)), width - 1u), 0u), 0).rgb;
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+}
+
+// Light a ray going in `ray_dir` brings back to `ray_origin`, its color in rgb and emission in
+// alpha.
+fn ray_radiance(ray_origin: vec2<f32>, ray_dir: vec2<f32>, ray_res: RaymarchResult, reso: vec2<f32>) -> vec4<f32> {
+    // The sky is infinitely far, only what the ray passed through dims it.
+    if (ray_res.escaped) {
+        let transmittance: vec3<f32> = ray_res.transmittance;
+        let sky_col: vec3<f32> = sky_radiance(ray_dir) * transmittance;
+        return vec4<f32>(sky_col, max(sky_col.r, max(sky_col.g, sky_col.b)));
+    }
+    if (!ray_res.hit) {
+        return vec4<f32>(0.0);
+    }
+    let pixel_surface: SurfaceResult = get_surface(ray_res.hit_pos, ray_origin);
+
+    // Light of the last bounce, emitters have no albedo so they don't bounce any.
+    var bounce: vec4<f32> = vec4<f32>(0.0);
+    // So light doesn't bounce off the surface it was emitted from.
+    if(gi_pass.bounce > 0u && ray_res.ray_dist >= 0.5 / max(reso.x, reso.y)) {
+        bounce = get_bounce_light(ray_res.hit_pos, reso);
+    }
+
+    // Falloff of the emitter that was hit, bounced light comes back from an occluder
+    // so the camera's falloff is used for it.
+    let range: f32 = select(settings.u_emission_range, pixel_surface.range, pixel_surface.emissive > 0.0);
+    let dropoff: f32 = select(settings.u_emission_dropoff, pixel_surface.dropoff, pixel_surface.emissive > 0.0);
+    let dist_att: f32 = pow(max(1.0 - (ray_res.ray_dist * ray_res.ray_dist) / (range * range), 0.0), dropoff);
+    // Tinted and attenuated by any translucent occluders on the way.
+    let ray_transmittance: vec3<f32> = ray_res.transmittance;
+    return vec4<f32>(
+        (pixel_surface.colour * pixel_surface.emissive + bounce.rgb) * dist_att * ray_transmittance,
+        (pixel_surface.emissive + bounce.a) * dist_att * max(ray_transmittance.r, max(ray_transmittance.g, ray_transmittance.b)),
     );
 }
 
-fn get_last_frame_data(uv: vec2<f32>, reso: vec2<f32>) -> SurfaceResult {
-  var last_emission: f32 = 0.0;
-  var last_col: vec3<f32> = vec3<f32>(0.0);
-  
-  for(var x: f32 = -1.0; x <= 1.0; x += 1.0) {
-      for(var y: f32 = -1.0; y <= 1.0; y += 1.0) {
-          let pixel = textureSample(history_texture, texture_sampler, uv + vec2<f32>(x, y) / reso);
-          if(pixel.a > last_emission){
-              last_emission = pixel.a;
-              last_col = pixel.rgb;
-          }
-      }
-  }
+// Radiance cascades: cascade `n` has a probe every 2^(n+1) pixels, each a block of 2^(n+1) by
+// 2^(n+1) texels holding one direction each. Its rays only cover the interval past the one of
+// cascade `n - 1`, what lies beyond comes from merging the four nearest probes of cascade `n + 1`.
+// Rays are evenly spread and never change, so the result has no noise.
+
+// Probe spacing and direction block size of a cascade, in pixels.
+fn cascade_block(index: u32) -> u32 {
+    return 2u << index;
+}
 
-  return SurfaceResult(
-    last_emission,
-    last_col
-  );
+// Start of the interval of a cascade, in pixels. Intervals grow 4 times per cascade.
+fn cascade_interval_start(index: u32) -> f32 {
+    return CASCADE_INTERVAL * (f32(1u << (2u * index)) - 1.0) / 3.0;
+}
+
+// The next cascade's radiance in the direction `direction` of a probe of the drawn cascade at
+// `position` pixels, as the average of the four directions it splits into, interpolated between
+// the four nearest probes.
+fn merge_upper_cascade(position: vec2<f32>, direction: u32) -> vec4<f32> {
+    let block: u32 = cascade_block(gi_pass.cascade + 1u);
+    let size: vec2<u32> = textureDimensions(cascade_texture, 0).xy;
+    let probes: vec2<i32> = vec2<i32>(size / block);
+    let probe_position: vec2<f32> = position / f32(block) - 0.5;
+    let base: vec2<i32> = vec2<i32>(floor(probe_position));
+    let weight: vec2<f32> = probe_position - vec2<f32>(base);
+
+    var merged: vec4<f32> = vec4<f32>(0.0);
+    for (var y: i32 = 0; y <= 1; y += 1) {
+        for (var x: i32 = 0; x <= 1; x += 1) {
+            let probe: vec2<u32> = vec2<u32>(clamp(base + vec2<i32>(x, y), vec2<i32>(0), probes - 1));
+            var radiance: vec4<f32> = vec4<f32>(0.0);
+            for (var i: u32 = 0u; i < 4u; i += 1u) {
+                let upper_direction: u32 = direction * 4u + i;
+                let texel: vec2<u32> = probe * block + vec2<u32>(upper_direction % block, upper_direction / block);
+                radiance += textureLoad(cascade_texture, texel, 0);
+            }
+            let bilinear: vec2<f32> = select(1.0 - weight, weight, vec2<i32>(x, y) == vec2<i32>(1));
+            merged += radiance * 0.25 * bilinear.x * bilinear.y;
+        }
+    }
+    return merged;
+}
+
+// GI of a pixel from cascade 0, the average of its directions interpolated between the four
+// nearest probes.
+fn gather_cascades(position: vec2<f32>) -> vec4<f32> {
+    let block: u32 = cascade_block(0u);
+    let size: vec2<u32> = textureDimensions(cascade_texture, 0).xy;
+    let probes: vec2<i32> = vec2<i32>(size / block);
+    let probe_position: vec2<f32> = position / f32(block) - 0.5;
+    let base: vec2<i32> = vec2<i32>(floor(probe_position));
+    let weight: vec2<f32> = probe_position - vec2<f32>(base);
+
+    var gathered: vec4<f32> = vec4<f32>(0.0);
+    for (var y: i32 = 0; y <= 1; y += 1) {
+        for (var x: i32 = 0; x <= 1; x += 1) {
+            let probe: vec2<u32> = vec2<u32>(clamp(base + vec2<i32>(x, y), vec2<i32>(0), probes - 1));
+            var radiance: vec4<f32> = vec4<f32>(0.0);
+            for (var i: u32 = 0u; i < block * block; i += 1u) {
+                radiance += textureLoad(cascade_texture, probe * block + vec2<u32>(i % block, i / block), 0);
+            }
+            let bilinear: vec2<f32> = select(1.0 - weight, weight, vec2<i32>(x, y) == vec2<i32>(1));
+            gathered += radiance / f32(block * block) * bilinear.x * bilinear.y;
+        }
+    }
+    return gathered;
+}
+
+// One cascade, the highest is drawn first so every other one can merge the one above it.
+@fragment
+fn cascade_fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let reso: vec2<f32> = vec2<f32>(textureDimensions(u_scene_data, 0).xy);
+    let pixel: vec2<u32> = vec2<u32>(in.position.xy);
+    let block: u32 = cascade_block(gi_pass.cascade);
+    let probe: vec2<u32> = pixel / block;
+    // Past the last whole probe.
+    if (any((probe + 1u) * block > vec2<u32>(reso))) {
+        return vec4<f32>(0.0);
+    }
+
+    let local: vec2<u32> = pixel % block;
+    let direction: u32 = local.y * block + local.x;
+    let angle: f32 = (f32(direction) + 0.5) / f32(block * block) * 2.0 * PI;
+    let ray_dir: vec2<f32> = vec2<f32>(cos(angle), sin(angle));
+    let position: vec2<f32> = (vec2<f32>(probe) + 0.5) * f32(block);
+    let ray_origin: vec2<f32> = position / reso;
+
+    // The distance field's units, the longer side is 1.
+    let texel: f32 = 1.0 / max(reso.x, reso.y);
+    let start: f32 = cascade_interval_start(gi_pass.cascade) * texel;
+    let end: f32 = cascade_interval_start(gi_pass.cascade + 1u) * texel;
+    let ray_res: RaymarchResult = raymarch_interval(ray_origin, ray_dir, reso, start, end);
+    var radiance: vec4<f32> = ray_radiance(ray_origin, ray_dir, ray_res, reso);
+
+    // Light from further away gets through when nothing opaque was hit in this interval and the
+    // ray is still in view.
+    if (!ray_res.hit && !ray_res.escaped && gi_pass.cascade + 1u < gi_pass.cascade_count) {
+        let transmittance: vec3<f32> = ray_res.transmittance;
+        let merged: vec4<f32> = merge_upper_cascade(position, direction);
+        radiance += vec4<f32>(
+            merged.rgb * transmittance,
+            merged.a * max(transmittance.r, max(transmittance.g, transmittance.b)),
+        );
+    }
+    return radiance;
+}
+
+// Signed distance field's gradient at `uv`, pointing away from the nearest edge inside the
+// surfaces and towards free space.
+fn distance_gradient(uv: vec2<f32>, reso: vec2<f32>) -> vec2<f32> {
+    let texel: vec2<f32> = 1.0 / reso;
+    return vec2<f32>(
+        textureSample(u_distance_data, texture_sampler, uv + vec2<f32>(texel.x, 0.0)).r
+            - textureSample(u_distance_data, texture_sampler, uv - vec2<f32>(texel.x, 0.0)).r,
+        textureSample(u_distance_data, texture_sampler, uv + vec2<f32>(0.0, texel.y)).r
+            - textureSample(u_distance_data, texture_sampler, uv - vec2<f32>(0.0, texel.y)).r,
+    );
+}
+
+struct OccluderLight {
+    // Where the light is gathered.
+    uv: vec2<f32>,
+    // How much of it reaches the pixel.
+    weight: f32,
+}
+// Light of an opaque occluder pixel `depth` inside its edge: the light of the free space just
+// past the nearest edge, found along the distance field's gradient, fading out towards
+// `u_light_penetration`.
+fn occluder_light(uv: vec2<f32>, depth: f32, reso: vec2<f32>) -> OccluderLight {
+    let gradient: vec2<f32> = distance_gradient(uv, reso);
+    if (depth >= settings.u_light_penetration || length(gradient) <= 0.0) {
+        return OccluderLight(uv, 0.0);
+    }
+
+    let texel: f32 = 1.0 / max(reso.x, reso.y);
+    let offset: f32 = depth + OCCLUDER_LIGHT_OFFSET * texel;
+    return OccluderLight(
+        uv + normalize(gradient) * offset / aspect(reso),
+        1.0 - depth / settings.u_light_penetration,
+    );
 }
 
 fn lin_to_srgb(color: vec4<f32>) -> vec3<f32> {
@@ -127,52 +585,70 @@ fn lin_to_srgb(color: vec4<f32>) -> vec3<f32> {
     return clr;
 }
 
+struct Output {
+    @location(0) gi: vec4<f32>,
+    // Read by the next bounce.
+    @location(1) surface_radiance: vec4<f32>,
+};
+
 @fragment
-fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+fn fragment(in: FullscreenVertexOutput) -> Output {
     let reso: vec2<f32> = vec2<f32>(textureDimensions(u_scene_data, 0).xy);
 
-    var pixel_emis: f32 = 0.0;
-    var pixel_col: vec3<f32> = vec3<f32>(0.0);
-    var rand_pixel_col: vec3<f32> = vec3<f32>(0.0);
+    // Bounces before the last are only read around the surfaces they hit.
+    let surface_distance: f32 = textureSample(u_distance_data, texture_sampler, in.uv).r / settings.u_dist_mod;
+    if (gi_pass.bounce < settings.u_max_bounces && surface_distance > 2.0 / max(reso.x, reso.y)) {
+        return Output(vec4<f32>(0.0), vec4<f32>(0.0));
+    }
 
-    let rand2pi: f32 = random(in.uv * vec2<f32>(time, -time)) * 2.0 * PI;
-    let golden_angle: f32 = PI * 0.7639320225; // Magic number for good ray distribution.
+    // Rays from inside an opaque occluder stop at once, it is lit from the free space next to it.
+    // Emitters keep their own color and rays see through the translucent occluders already.
+    var origin: vec2<f32> = in.uv;
+    var light_weight: f32 = 1.0;
+    let texel: f32 = 1.0 / max(reso.x, reso.y);
+    let scene: vec3<f32> = textureSample(u_scene_data, texture_sampler, in.uv).rgb;
+    let transmittance: vec3<f32> = textureSample(u_scene_transmittance, texture_sampler, in.uv).rgb;
+    if (surface_distance < 0.5 * texel
+        && max(scene.r, max(scene.g, scene.b)) <= 0.0
+        && max(transmittance.r, max(transmittance.g, transmittance.b)) <= 0.0) {
+        let occluder: OccluderLight = occluder_light(in.uv, max(-surface_distance, 0.0), reso);
+        if (occluder.weight <= 0.0) {
+            return Output(vec4<f32>(0.0), vec4<f32>(0.0));
+        }
+        origin = occluder.uv;
+        light_weight = occluder.weight;
+    }
 
-    var hit_col: vec3<f32> = vec3<f32>(0.0);
-
-    // Cast our rays.
-    for(var i: i32 = 0; i < settings.u_rays_per_pixel; i = i + 1) {
-        // Get our ray dir by taking the random angle and adding golden_angle * ray number.
-        let cur_angle: f32 = rand2pi + golden_angle * f32(i);
-        let ray_dir: vec2<f32> = normalize(vec2<f32>(cos(cur_angle), sin(cur_angle)));
-        let ray_origin: vec2<f32> = in.uv;
-
-        var ray_res: RaymarchResult = raymarch(ray_origin, ray_dir, time, reso);
-        if(ray_res.hit) {
-            let pixel_surface: SurfaceResult = get_surface(ray_res.hit_pos, ray_origin);
-
-            // GI using nearest pixels from last frame.
-            var last_emission: f32 = 0.0;
-            var last_col: vec3<f32> = vec3<f32>(0.0);
-            // Don't want emissive surfaces themselves to bounce light
-            if(pixel_surface.emissive < 0.5 / max(reso.x, reso.y)) {
-                let last_frame_data: SurfaceResult = get_last_frame_data(ray_res.hit_pos, reso);
-                last_emission = last_frame_data.emissive;
-                last_col = last_frame_data.colour;
-            }
-            // So light doesn't bounce off the surface it was emitted from.
-            if(ray_res.ray_dist < 0.5 / max(reso.x, reso.y)) {
-                last_emission = 0.0;
-            }
+    var pixel_emis: f32 = 0.0;
+    var pixel_col: vec3<f32> = vec3<f32>(0.0);
 
-            let dist_att: f32 = pow(max(1.0 - (ray_res.ray_dist * ray_res.ray_dist) / (settings.u_emission_range * settings.u_emission_range), 0.0), settings.u_emission_dropoff);
-            pixel_emis += (pixel_surface.emissive + last_emission) * dist_att;
-            pixel_col += (pixel_surface.colour + last_col) * (pixel_surface.emissive + last_emission) * dist_att;
+    if (gi_pass.mode == GI_MODE_RADIANCE_CASCADES) {
+        let gathered: vec4<f32> = gather_cascades(origin * reso);
+        pixel_col = gathered.rgb;
+        pixel_emis = gathered.a;
+    } else {
+        let pixel: vec2<u32> = vec2<u32>(in.position.xy);
+        let rotation: f32 = ray_rotation(pixel);
+
+        // Cast our rays.
+        for(var i: i32 = 0; i < settings.u_rays_per_pixel; i = i + 1) {
+            let cur_angle: f32 = ray_angle(pixel, rotation, i);
+            let ray_dir: vec2<f32> = normalize(vec2<f32>(cos(cur_angle), sin(cur_angle)));
+            let ray_origin: vec2<f32> = origin;
+
+            let radiance: vec4<f32> = ray_radiance(ray_origin, ray_dir, raymarch(ray_origin, ray_dir, reso), reso);
+            pixel_col += radiance.rgb;
+            pixel_emis += radiance.a;
         }
+
+        pixel_emis /= f32(settings.u_rays_per_pixel);
+        pixel_col /= f32(settings.u_rays_per_pixel);
     }
 
-    pixel_emis /= f32(settings.u_rays_per_pixel);
-    pixel_col /= f32(settings.u_rays_per_pixel);
+    // Analytic lights don't need rays to find them.
+    let spot_col: vec3<f32> = get_spot_lights(origin, reso);
+    pixel_col += spot_col;
+    pixel_emis += max(spot_col.r, max(spot_col.g, spot_col.b));
 
 
     // Color correction and filters.
@@ -181,5 +657,6 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
     // pixel_col *= (1.0 / (1.0 + pixel_col * 0.5));
 
     // Emissive in alpha.
-    return vec4<f32>(pixel_col, pixel_emis);
+    let gi: vec4<f32> = vec4<f32>(pixel_col, pixel_emis) * light_weight;
+    return Output(gi, gi);
 }
\ No newline at end of file
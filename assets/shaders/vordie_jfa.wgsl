@@ -1,5 +1,6 @@
 
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import "shaders/vordie_jfa_functions.wgsl"::jfa_step
 
 @group(0) @binding(0)
 var screen_texture: texture_2d<f32>;
@@ -24,24 +25,5 @@ struct Params {
 
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    var closest_dist: f32 = 9999999.9;
-    var closest_pos: vec2<f32> = vec2<f32>(0.0, 0.0);
-
-    let uv: vec2<f32> = in.uv;
-
-    for(var x: f32 = -1.0; x <= 1.0; x += 1.0) {
-        for(var y: f32 = -1.0; y <= 1.0; y += 1.0) {
-            let voffset: vec2<f32> = uv + (vec2<f32>(x, y) * params.offset / params.screen_pixel_size);
-
-            let pos: vec2<f32> = textureSample(screen_texture, texture_sampler, voffset).xy;
-            let dist: f32 = length(pos - uv);
-
-            if(pos.x != 0.0 && pos.y != 0.0 && dist < closest_dist) {
-                closest_dist = dist;
-                closest_pos = pos;
-            }
-        }
-    }
-    
-    return vec4<f32>(closest_pos, 0.0, 1.0);
-}
\ No newline at end of file
+    return jfa_step(screen_texture, texture_sampler, in.uv, params.offset, params.screen_pixel_size);
+}
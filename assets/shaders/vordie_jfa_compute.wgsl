@@ -0,0 +1,87 @@
+#import "shaders/vordie_jfa_functions.wgsl"::{jfa_seed, jfa_step, jfa_signed_distance}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+struct VordieLightSettings {
+    u_rays_per_pixel: i32,
+    u_emission_multi: f32,
+    u_max_raymarch_steps: i32,
+    u_dist_mod: f32,
+}
+@group(0) @binding(2) var<uniform> settings: VordieLightSettings;
+
+struct Params {
+    screen_pixel_size: vec2<f32>,
+    offset: f32
+}
+@group(0) @binding(3) var<uniform> params: Params;
+
+#ifdef JFA_FULL_PRECISION
+@group(0) @binding(4)
+var destination_texture: texture_storage_2d<rgba32float, write>;
+#else
+@group(0) @binding(4)
+var destination_texture: texture_storage_2d<rgba16float, write>;
+#endif
+
+// Only read by the distance field pass.
+@group(0) @binding(5)
+var inverse_source_texture: texture_2d<f32>;
+
+// UV of the pixel center, the same as the fullscreen triangle gives the fragment backend.
+fn pixel_uv(id: vec2<u32>) -> vec2<f32> {
+    return (vec2<f32>(id) + 0.5) / vec2<f32>(textureDimensions(destination_texture));
+}
+
+fn in_bounds(id: vec2<u32>) -> bool {
+    return all(id < textureDimensions(destination_texture));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn seed(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id.xy)) {
+        return;
+    }
+    textureStore(destination_texture, id.xy, jfa_seed(source_texture, texture_sampler, pixel_uv(id.xy), false));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn inverse_seed(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id.xy)) {
+        return;
+    }
+    textureStore(destination_texture, id.xy, jfa_seed(source_texture, texture_sampler, pixel_uv(id.xy), true));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn jump_flood(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id.xy)) {
+        return;
+    }
+    textureStore(
+        destination_texture,
+        id.xy,
+        jfa_step(source_texture, texture_sampler, pixel_uv(id.xy), params.offset, params.screen_pixel_size),
+    );
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn distance_field(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (!in_bounds(id.xy)) {
+        return;
+    }
+    textureStore(
+        destination_texture,
+        id.xy,
+        jfa_signed_distance(
+            source_texture,
+            inverse_source_texture,
+            texture_sampler,
+            pixel_uv(id.xy),
+            settings.u_dist_mod,
+        ),
+    );
+}
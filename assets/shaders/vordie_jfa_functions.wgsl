@@ -0,0 +1,80 @@
+// Shared by the fragment and compute backends, so both produce the same output.
+// Imported by asset path, so bevy waits for this file before compiling the pipelines using it.
+
+// Distances are measured in aspect-corrected UV, where the longer side of the texture is 1.0 and
+// both axes use the same unit, so the field stays round on non-square targets.
+fn jfa_aspect(size: vec2<f32>) -> vec2<f32> {
+    return size / max(size.x, size.y);
+}
+
+// Seeds are stored as (uv, valid, 1.0), so a seed at UV 0 on either axis is still a seed.
+const JFA_NO_SEED: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+
+// Seeds every surface pixel with its own position, empty pixels with no seed. `inverted` seeds the
+// empty pixels instead, flooding those gives the distance from inside a surface to its edge.
+fn jfa_seed(scene_texture: texture_2d<f32>, texture_sampler: sampler, uv: vec2<f32>, inverted: bool) -> vec4<f32> {
+    let in_diffuse = textureSampleLevel(scene_texture, texture_sampler, uv, 0.0);
+    if ((in_diffuse.a > 0.0) == inverted) {
+        return JFA_NO_SEED;
+    }
+
+    return vec4<f32>(uv, 1.0, 1.0);
+}
+
+// One jump flood pass, keeps the closest seed of the 3x3 neighbours `offset` pixels away.
+fn jfa_step(
+    source_texture: texture_2d<f32>,
+    texture_sampler: sampler,
+    uv: vec2<f32>,
+    offset: f32,
+    screen_pixel_size: vec2<f32>,
+) -> vec4<f32> {
+    let aspect: vec2<f32> = jfa_aspect(screen_pixel_size);
+    var closest_dist: f32 = 9999999.9;
+    var closest: vec4<f32> = JFA_NO_SEED;
+
+    for(var x: f32 = -1.0; x <= 1.0; x += 1.0) {
+        for(var y: f32 = -1.0; y <= 1.0; y += 1.0) {
+            let voffset: vec2<f32> = uv + (vec2<f32>(x, y) * offset / screen_pixel_size);
+
+            let seed: vec4<f32> = textureSampleLevel(source_texture, texture_sampler, voffset, 0.0);
+            let dist: f32 = length((seed.xy - uv) * aspect);
+
+            if(seed.z > 0.5 && dist < closest_dist) {
+                closest_dist = dist;
+                closest = seed;
+            }
+        }
+    }
+
+    return closest;
+}
+
+// Distance to the closest seed found by the jump flood, scaled by `dist_mod` and clamped to 1.0.
+fn jfa_seed_distance(voronoi_texture: texture_2d<f32>, texture_sampler: sampler, uv: vec2<f32>, dist_mod: f32) -> f32 {
+    let in_diffuse = textureSampleLevel(voronoi_texture, texture_sampler, uv, 0.0);
+    // Without any seed on screen nothing is near.
+    if (in_diffuse.z <= 0.5) {
+        return 1.0;
+    }
+
+    let aspect: vec2<f32> = jfa_aspect(vec2<f32>(textureDimensions(voronoi_texture, 0).xy));
+    let dist: f32 = distance(in_diffuse.xy * aspect, uv * aspect);
+    return clamp(dist * dist_mod, 0.0, 1.0);
+}
+
+// Signed distance to the nearest surface edge, positive outside and negative inside the surfaces.
+// One of the two floods always finds the pixel itself, so only the other one adds to the result.
+fn jfa_signed_distance(
+    voronoi_texture: texture_2d<f32>,
+    inverse_voronoi_texture: texture_2d<f32>,
+    texture_sampler: sampler,
+    uv: vec2<f32>,
+    dist_mod: f32,
+) -> vec4<f32> {
+    let outside: f32 = jfa_seed_distance(voronoi_texture, texture_sampler, uv, dist_mod);
+    let inside: f32 = jfa_seed_distance(inverse_voronoi_texture, texture_sampler, uv, dist_mod);
+    let mapped: f32 = outside - inside;
+
+    return vec4<f32>(vec3<f32>(mapped), 1.0);
+}
@@ -1,5 +1,6 @@
 
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import "shaders/vordie_jfa_functions.wgsl"::jfa_seed
 
 @group(0) @binding(0)
 var screen_texture: texture_2d<f32>;
@@ -18,13 +19,9 @@ struct VordieLightSettings {
 
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    let in_diffuse   = textureSample(screen_texture, texture_sampler, in.uv);
-
-    return vec4<f32>(
-        in.uv.x * in_diffuse.a,
-        in.uv.y * in_diffuse.a,
-        0.0,
-        1.0
-    );
-
-}
\ No newline at end of file
+#ifdef INVERT_MASK
+    return jfa_seed(screen_texture, texture_sampler, in.uv, true);
+#else
+    return jfa_seed(screen_texture, texture_sampler, in.uv, false);
+#endif
+}
@@ -0,0 +1,57 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0)
+var light_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var scene_texture: texture_2d<f32>;
+
+struct VordieLightSettings {
+    u_rays_per_pixel: i32,
+    u_emission_multi: f32,
+    u_max_raymarch_steps: i32,
+    u_dist_mod: f32,
+    u_emission_range: f32,
+    u_emission_dropoff: f32,
+    u_ambient: vec3<f32>,
+    u_upsample_edge_sensitivity: f32,
+}
+@group(0) @binding(2) var<uniform> settings: VordieLightSettings;
+
+fn scene_at(uv: vec2<f32>) -> vec3<f32> {
+    let size: vec2<u32> = textureDimensions(scene_texture, 0).xy;
+    let coord: vec2<u32> = min(vec2<u32>(uv * vec2<f32>(size)), size - 1u);
+    return textureLoad(scene_texture, coord, 0).rgb;
+}
+
+// Joint bilateral upsample: the four nearest light texels are weighted bilinearly, and by how
+// close the scene color under each of them is to the scene color of this pixel. Light doesn't
+// bleed over an occluder's edge onto the floor next to it.
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let light_size: vec2<f32> = vec2<f32>(textureDimensions(light_texture, 0).xy);
+    let guide: vec3<f32> = scene_at(in.uv);
+
+    let texel: vec2<f32> = in.uv * light_size - 0.5;
+    let base: vec2<f32> = floor(texel);
+    let bilinear: vec2<f32> = texel - base;
+
+    var total: vec4<f32> = vec4<f32>(0.0);
+    var total_weight: f32 = 0.0;
+    for (var y: i32 = 0; y <= 1; y = y + 1) {
+        for (var x: i32 = 0; x <= 1; x = x + 1) {
+            let tap: vec2<f32> = clamp(base + vec2<f32>(f32(x), f32(y)), vec2<f32>(0.0), light_size - 1.0);
+            let tap_uv: vec2<f32> = (tap + 0.5) / light_size;
+
+            let spatial: vec2<f32> = select(1.0 - bilinear, bilinear, vec2<bool>(x == 1, y == 1));
+            // A little of the bilinear weight is kept, so pixels unlike all four taps still get light.
+            let range: f32 = exp(-settings.u_upsample_edge_sensitivity * distance(scene_at(tap_uv), guide)) + 0.0001;
+            let weight: f32 = spatial.x * spatial.y * range;
+
+            total += textureLoad(light_texture, vec2<u32>(tap), 0) * weight;
+            total_weight += weight;
+        }
+    }
+
+    return total / max(total_weight, 0.000001);
+}
@@ -0,0 +1,104 @@
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping, prelude::*, render::camera::RenderTarget,
+    sprite::MaterialMesh2dBundle, window::WindowRef,
+};
+use ray_light_2d::prelude::*;
+
+// The same scene lit by both GI modes, one camera per window. Every camera has its own
+// `VordieGiMode`, so the modes can be compared side by side.
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::srgb(0.6, 0.6, 0.6)))
+        // ! MSAA needs to be off for the jump flood to work for now
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        resolution: (640., 540.).into(),
+                        title: "Vordie: Raymarch".into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .set(ImagePlugin::default_nearest()),
+            VordieLight2DPlugin {
+                order: VordieLightPassOrder::BeforeTonemapping,
+                ..default()
+            },
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let second_window = commands
+        .spawn(Window {
+            resolution: (640., 540.).into(),
+            title: "Vordie: Radiance cascades".into(),
+            ..default()
+        })
+        .id();
+
+    for (target, gi_mode) in [
+        (WindowRef::Primary, VordieGiMode::Raymarch),
+        (
+            WindowRef::Entity(second_window),
+            VordieGiMode::RadianceCascades,
+        ),
+    ] {
+        commands.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    hdr: true,
+                    target: RenderTarget::Window(target),
+                    ..default()
+                },
+                tonemapping: Tonemapping::TonyMcMapface,
+                ..default()
+            },
+            VordieLightSettings {
+                u_ambient: Vec3::splat(0.02),
+                ..default()
+            },
+            gi_mode,
+        ));
+    }
+
+    // Light
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Circle::new(10.)).into(),
+            material: materials.add(Color::srgb(1.0, 0.6, 0.2)),
+            transform: Transform::from_translation(Vec3::new(-120., 0., 0.)),
+            ..default()
+        },
+        VordieEmitter::default(),
+    ));
+
+    // Light occluder, casting a shadow to the right
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(20., 160.)).into(),
+            material: materials.add(Color::srgb(0.3, 0.3, 0.3)),
+            transform: Transform::from_translation(Vec3::new(0., 0., 0.)),
+            ..default()
+        },
+        VordieOccluder::default(),
+    ));
+
+    // Light occluder below the light
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(240., 10.)).into(),
+            material: materials.add(Color::srgb(0.3, 0.3, 0.3)),
+            transform: Transform::from_translation(Vec3::new(-60., -120., 0.)),
+            ..default()
+        },
+        VordieOccluder::default(),
+    ));
+}
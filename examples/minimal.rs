@@ -2,7 +2,6 @@ use bevy::{
     core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
     prelude::*,
     sprite::MaterialMesh2dBundle,
-    window::WindowResolution,
 };
 // use bevy::render::camera::RenderTarget;
 use ray_light_2d::prelude::*;
@@ -13,27 +12,30 @@ pub struct SpriteCamera;
 fn main() {
     // Basic setup.
     App::new()
-        .insert_resource(ClearColor(Color::srgba(0.0, 0.0, 0.0, 0.0)))
+        .insert_resource(ClearColor(Color::srgb(0.6, 0.6, 0.6)))
         // ! MSAA needs to be off for the jump flood to work for now
         .insert_resource(Msaa::Off)
         .add_plugins((
             DefaultPlugins
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        resolution: (500., 500.).into(),
-                        // resolution: WindowResolution::new(1600., 1600.)
+                        resolution: (960., 540.).into(),
+                        // resolution: bevy::window::WindowResolution::new(1600., 1600.)
                         //     .with_scale_factor_override(2.),
                         title: "Vordie: Minimal Example".into(),
-                        resizable: false,
                         ..default()
                     }),
                     ..default()
                 })
                 .set(ImagePlugin::default_nearest()),
-            VordieLight2DPlugin,
+            // Light the HDR scene, so bloom and tonemapping apply to it
+            VordieLight2DPlugin {
+                order: VordieLightPassOrder::BeforeTonemapping,
+                ..default()
+            },
         ))
         .add_systems(Startup, setup)
-        .add_systems(Update, system_move_camera)
+        .add_systems(Update, (system_move_camera, system_debug_view))
         .run();
 }
 
@@ -54,52 +56,116 @@ fn setup(
                 ..default()
             },
             BloomSettings::NATURAL, // 3. Enable bloom for the camera
-            VordieLightSettings { ..default() },
+            VordieDebugView::default(),
+            // Light at half the window size, upsampled along the scene's edges
+            VordieResolutionScale::Half,
+            // Less grain than random ray angles for the same number of rays
+            VordieRaySampling::BlueNoise,
+            VordieLightSettings {
+                // The clear color acts as the floor, keep it faintly visible in the dark
+                u_ambient: Vec3::splat(0.02),
+                ..default()
+            },
         ))
         .insert(SpriteCamera);
 
     // Light
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(Circle::new(10.)).into(),
-        // 4. Put something bright in a dark environment to see the effect
-        material: materials.add(Color::srgb(0.5, 0.0, 0.9)),
-        transform: Transform::from_translation(Vec3::new(-100., -100., 0.)),
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Circle::new(10.)).into(),
+            // 4. Put something bright in a dark environment to see the effect
+            material: materials.add(Color::srgb(0.5, 0.0, 0.9)),
+            transform: Transform::from_translation(Vec3::new(-100., -100., 0.)),
+            ..default()
+        },
+        VordieEmitter::default(),
+    ));
+
+    // Light
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Circle::new(10.)).into(),
+            material: materials.add(Color::srgb(1.0, 0.0, 0.5)),
+            transform: Transform::from_translation(Vec3::new(100., 100., 0.)),
+            ..default()
+        },
+        // Brighter, but fades out sooner than the camera's settings
+        VordieEmitter {
+            intensity: 1.5,
+            range: Some(0.6),
+            dropoff: Some(2.0),
+        },
+    ));
+
+    // Light without any geometry on screen
+    commands.spawn(VordiePointLightBundle {
+        point_light: VordiePointLight {
+            color: Color::srgb(1.0, 0.8, 0.4),
+            intensity: 1.0,
+            radius: 8.0,
+        },
+        transform: Transform::from_translation(Vec3::new(100., -100., 0.)),
         ..default()
     });
 
-    // Light
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(Circle::new(10.)).into(),
-        material: materials.add(Color::srgb(1.0, 0.0, 0.5)),
-        transform: Transform::from_translation(Vec3::new(100., 100., 0.)),
+    // Cone of light, e.g. a flashlight
+    commands.spawn(VordieSpotLightBundle {
+        spot_light: VordieSpotLight {
+            color: Color::srgb(0.9, 0.9, 1.0),
+            direction: Vec2::new(1.0, -1.0),
+            ..default()
+        },
+        transform: Transform::from_translation(Vec3::new(-100., 100., 0.)),
         ..default()
     });
 
     // Walls
 
     // Light occluder top
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(Rectangle::new(300., 10.)).into(),
-        material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
-        transform: Transform::from_translation(Vec3::new(0., 150., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(300., 10.)).into(),
+            material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
+            transform: Transform::from_translation(Vec3::new(0., 150., 0.)),
+            ..default()
+        },
+        VordieOccluder::default(),
+    ));
 
     // Light occluder left
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(Rectangle::new(10., 250.)).into(),
-        material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
-        transform: Transform::from_translation(Vec3::new(-150., 50., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(10., 250.)).into(),
+            material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
+            transform: Transform::from_translation(Vec3::new(-150., 50., 0.)),
+            ..default()
+        },
+        VordieOccluder::default(),
+    ));
+
+    // Red window on the right, lets red light through
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(10., 250.)).into(),
+            material: materials.add(Color::srgb(0.6, 0.0, 0.0)),
+            transform: Transform::from_translation(Vec3::new(150., 50., 0.)),
+            ..default()
+        },
+        VordieOccluder {
+            transmittance: Color::srgb(0.95, 0.3, 0.3),
+        },
+    ));
 
     // Light occluder bottom
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(Rectangle::new(300., 10.)).into(),
-        material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
-        transform: Transform::from_translation(Vec3::new(0., -150., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(300., 10.)).into(),
+            material: materials.add(Color::srgb(0.0, 0.0, 0.0)),
+            transform: Transform::from_translation(Vec3::new(0., -150., 0.)),
+            ..default()
+        },
+        VordieOccluder::default(),
+    ));
 }
 
 fn system_move_camera(
@@ -131,3 +197,27 @@ fn system_move_camera(
         camera_transform.translation.y += movement.y;
     }
 }
+
+// Number keys pick the buffer shown, to tune the settings by eye.
+fn system_debug_view(
+    mut query_camera: Query<&mut VordieDebugView>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    let views = [
+        (KeyCode::Digit1, VordieDebugView::Scene),
+        (KeyCode::Digit2, VordieDebugView::Seeds),
+        (KeyCode::Digit3, VordieDebugView::Voronoi),
+        (KeyCode::Digit4, VordieDebugView::DistanceField),
+        (KeyCode::Digit5, VordieDebugView::Gi),
+        (KeyCode::Digit6, VordieDebugView::History),
+        (KeyCode::Digit7, VordieDebugView::Final),
+    ];
+
+    for mut debug_view in &mut query_camera {
+        for (key, view) in views {
+            if keyboard.just_pressed(key) {
+                *debug_view = view;
+            }
+        }
+    }
+}
@@ -4,38 +4,110 @@ use bevy::{
         core_2d::graph::{Core2d, Node2d},
         fullscreen_vertex_shader::fullscreen_shader_vertex_state,
     },
-    ecs::query::QueryItem,
+    ecs::{entity::EntityHashMap, query::QueryItem},
     prelude::*,
     render::{
         camera::ExtractedCamera,
-        extract_component::{
-            ComponentUniforms, ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin,
-        },
-        render_graph::{
-            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
-        },
+        extract_component::{ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin},
+        render_graph::{InternedRenderLabel, RenderGraphApp, RenderLabel, ViewNodeRunner},
         render_resource::{
-            binding_types::{sampler, texture_2d, uniform_buffer},
+            binding_types::{
+                sampler, storage_buffer_read_only, texture_2d, texture_storage_2d, uniform_buffer,
+            },
             *,
         },
-        renderer::{RenderContext, RenderDevice, RenderQueue},
-        texture::{BevyDefault, CachedTexture, TextureCache},
-        view::{ExtractedView, ViewTarget},
-        Render, RenderApp, RenderSet,
+        renderer::RenderDevice,
+        texture::{BevyDefault, CachedTexture, ImageLoaderSettings, TextureCache},
+        view::{ExtractedView, ViewTarget, ViewUniform},
+        ExtractSchedule, MainWorld, Render, RenderApp, RenderSet,
     },
 };
 
-// Testing by step
-const STEP: i32 = 5;
+mod lights;
+mod node;
+mod scene;
+#[cfg(test)]
+mod tests;
+
+use lights::GpuVordieSpotLights;
+use node::{
+    VordieCompositeNode, VordieDenoiseNode, VordieDistanceFieldNode, VordieGiRaycastNode,
+    VordieJumpFloodNode, VordieSceneNode, VordieSeedNode, VordieUpsampleNode,
+};
+use scene::GpuVordieEmitters;
+
+pub use lights::{
+    VordiePointLight, VordiePointLightBundle, VordieSpotLight, VordieSpotLightBundle,
+};
+pub use node::VordieNode2d;
+pub use scene::{VordieEmitter, VordieOccluder, VordieScene2d};
+
+// encase 0.8's `ShaderType` derive checks each field from a closure that is never called, and
+// rustc reports those checks as dead code on the enclosing module rather than on the struct, so
+// each derive gets a module of its own to scope the allow.
+#[allow(dead_code)]
+mod vordie_light_settings {
+    use super::*;
 
-#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
-pub struct VordieLightSettings {
-    pub u_rays_per_pixel: i32,
-    pub u_emission_multi: f32,
-    pub u_max_raymarch_steps: i32,
-    pub u_dist_mod: f32,
-    pub u_emission_range: f32,
-    pub u_emission_dropoff: f32,
+    #[derive(Component, Clone, Copy, ShaderType)]
+    pub struct VordieLightSettings {
+        pub u_rays_per_pixel: i32,
+        pub u_emission_multi: f32,
+        pub u_max_raymarch_steps: i32,
+        pub u_dist_mod: f32,
+        pub u_emission_range: f32,
+        pub u_emission_dropoff: f32,
+        /// Light added everywhere on top of the GI, so unlit sprites don't go fully black.
+        pub u_ambient: Vec3,
+        /// How strongly scene color changes stop the GI from being blurred across them when it's
+        /// upsampled to the view size. `0.0` is a plain bilinear upsample.
+        pub u_upsample_edge_sensitivity: f32,
+        /// Seeds the GI noise together with the frame number, the same seed renders the same noise
+        /// on the same frame.
+        pub u_noise_seed: u32,
+        /// À-trous passes of the denoiser, each one doubles the filter footprint. 0 leaves only the
+        /// temporal accumulation, which blends in at most the last 5 frames so it still follows
+        /// moving lights. Capped at [`Self::MAX_DENOISE_ITERATIONS`].
+        pub u_denoise_iterations: u32,
+        /// How strongly differences in the scene colors stop the denoiser from blurring across
+        /// surface edges.
+        pub u_denoise_edge_sensitivity: f32,
+        /// Multiplier on the light bounced off the occluders, on top of their albedo.
+        pub u_bounce_intensity: f32,
+        /// Times light can bounce off the occluders before it reaches a pixel. 0 only lights the
+        /// view directly. Every bounce runs the GI pass, and with radiance cascades every cascade,
+        /// once more, so each one costs about as much as the whole unbounced GI. Capped at
+        /// [`Self::MAX_BOUNCES`].
+        pub u_max_bounces: u32,
+        /// How deep into the opaque occluders the light of the free space next to them reaches,
+        /// fading out towards it. In the units of the distance field, the longer side of the view
+        /// is 1. 0 leaves the occluders black.
+        pub u_light_penetration: f32,
+    }
+}
+pub use vordie_light_settings::VordieLightSettings;
+impl VordieLightSettings {
+    /// Most bounces `u_max_bounces` runs, past it the extra GI passes would stall the GPU for
+    /// light too dim to see.
+    pub const MAX_BOUNCES: u32 = 4;
+    /// Most à-trous passes `u_denoise_iterations` runs, past it the taps land too far apart to
+    /// find the same surface.
+    pub const MAX_DENOISE_ITERATIONS: u32 = 5;
+}
+impl ExtractComponent for VordieLightSettings {
+    type QueryData = &'static Self;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(settings: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(Self {
+            u_denoise_iterations: settings
+                .u_denoise_iterations
+                .min(Self::MAX_DENOISE_ITERATIONS),
+            u_max_bounces: settings.u_max_bounces.min(Self::MAX_BOUNCES),
+            ..*settings
+        })
+    }
 }
 impl Default for VordieLightSettings {
     fn default() -> Self {
@@ -46,14 +118,308 @@ impl Default for VordieLightSettings {
             u_dist_mod: 1.0,
             u_emission_range: 1.5,
             u_emission_dropoff: 1.5,
+            u_ambient: Vec3::ZERO,
+            u_upsample_edge_sensitivity: 10.0,
+            u_noise_seed: 0,
+            u_denoise_iterations: 3,
+            u_denoise_edge_sensitivity: 10.0,
+            u_bounce_intensity: 1.0,
+            u_max_bounces: 0,
+            u_light_penetration: 0.02,
+        }
+    }
+}
+
+/// Shows one of the light pass buffers on a camera instead of the lit scene, remapped to a
+/// viewable range.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, ExtractComponent)]
+pub enum VordieDebugView {
+    /// Emitters in their color, occluders in grey.
+    Scene,
+    /// Jump flood seeds, their UV as red and green.
+    Seeds,
+    /// Nearest seed of each pixel, its UV as red and green.
+    Voronoi,
+    /// Signed distance to the nearest surface edge as it's stored, the longer side of the view
+    /// is 1. Inside the surfaces in red.
+    DistanceField,
+    /// GI before denoising.
+    Gi,
+    /// Temporally accumulated GI of the last frame, before the à-trous passes.
+    History,
+    /// The lit scene.
+    #[default]
+    Final,
+}
+
+/// How the GI rays of a pixel are spread around it. Set per camera, all of them cast the same
+/// number of rays.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, ExtractComponent)]
+pub enum VordieRaySampling {
+    /// A random rotation per pixel and frame, with the rays a golden angle apart.
+    #[default]
+    WhiteNoise,
+    /// Evenly spaced slices of the circle, with a ray at a random angle in each.
+    Stratified,
+    /// Evenly spaced rays, rotated per pixel by the R2 low discrepancy sequence.
+    R2,
+    /// Evenly spaced rays, rotated per pixel by a tiled blue noise texture.
+    BlueNoise,
+}
+
+/// How the GI of a camera is computed.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, ExtractComponent)]
+pub enum VordieGiMode {
+    /// Casts `u_rays_per_pixel` randomly rotated rays from every pixel and leaves the noise to
+    /// the denoiser.
+    #[default]
+    Raymarch,
+    /// Merges radiance cascades, evenly spread rays from a grid of probes that get sparser and
+    /// see further with every cascade. Free of noise, its cost follows the lighting resolution
+    /// rather than `u_rays_per_pixel`.
+    RadianceCascades,
+}
+
+/// Light of the GI rays that leave the view without hitting anything, so open areas are lit by
+/// the sky and overhangs cast shadows. Set per camera, without one those rays bring no light.
+#[derive(Component, Clone, Debug, PartialEq, ExtractComponent)]
+pub enum VordieSky {
+    /// The same color in every direction.
+    Color(Color),
+    /// Blends from `down` for rays going straight down the screen to `up` for rays going up.
+    Gradient { up: Color, down: Color },
+    /// A 2D texture one row high, e.g. 256x1, around the view: its width covers a full turn
+    /// counter-clockwise, starting from rays going right. Taller textures only have their first
+    /// row read.
+    Texture(Handle<Image>),
+}
+
+#[allow(dead_code)]
+mod vordie_sky_params {
+    use super::*;
+
+    /// A [`VordieSky`] as the GI pass reads it. Mode 0 is no sky, 1 the gradient from `down` to
+    /// `up`, which are the same for a single color, and 2 the texture.
+    #[derive(Clone, Copy, Default, ShaderType)]
+    pub(crate) struct VordieSkyParams {
+        pub mode: u32,
+        pub up: Vec4,
+        pub down: Vec4,
+    }
+}
+pub(crate) use vordie_sky_params::VordieSkyParams;
+impl From<Option<&VordieSky>> for VordieSkyParams {
+    fn from(sky: Option<&VordieSky>) -> Self {
+        match sky {
+            None => Self::default(),
+            Some(VordieSky::Color(color)) => Self {
+                mode: 1,
+                up: LinearRgba::from(*color).to_vec4(),
+                down: LinearRgba::from(*color).to_vec4(),
+            },
+            Some(VordieSky::Gradient { up, down }) => Self {
+                mode: 1,
+                up: LinearRgba::from(*up).to_vec4(),
+                down: LinearRgba::from(*down).to_vec4(),
+            },
+            Some(VordieSky::Texture(_)) => Self {
+                mode: 2,
+                ..default()
+            },
+        }
+    }
+}
+
+#[allow(dead_code)]
+mod vordie_noise_params {
+    use super::*;
+
+    /// Frame and sampling strategy of the GI noise.
+    #[derive(Clone, Copy, ShaderType)]
+    pub(crate) struct VordieNoiseParams {
+        pub frame: u32,
+        pub sampling: u32,
+    }
+}
+pub(crate) use vordie_noise_params::VordieNoiseParams;
+
+#[allow(dead_code)]
+mod vordie_gi_pass_params {
+    use super::*;
+
+    /// What a GI pass draws: the bounce, and with radiance cascades the cascade out of how many.
+    /// `mode` is a [`VordieGiMode`].
+    #[derive(Clone, Copy, Default, ShaderType)]
+    pub(crate) struct VordieGiPassParams {
+        pub cascade: u32,
+        pub cascade_count: u32,
+        pub bounce: u32,
+        pub mode: u32,
+    }
+}
+pub(crate) use vordie_gi_pass_params::VordieGiPassParams;
+
+/// Resolution the emitters, occluders and GI are rendered at, relative to the view. The light is
+/// upsampled back to the view size, guided by the scene colors.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, ExtractComponent)]
+pub enum VordieResolutionScale {
+    Full,
+    #[default]
+    Half,
+    Quarter,
+    /// Any fraction of the view size.
+    Custom(f32),
+}
+impl VordieResolutionScale {
+    pub fn factor(self) -> f32 {
+        match self {
+            Self::Full => 1.0,
+            Self::Half => 0.5,
+            Self::Quarter => 0.25,
+            Self::Custom(factor) => factor,
+        }
+    }
+
+    /// Size of the lighting textures for a view of `size`, at least one pixel.
+    pub fn apply(self, size: UVec2) -> UVec2 {
+        (size.as_vec2() * self.factor())
+            .round()
+            .as_uvec2()
+            .max(UVec2::ONE)
+    }
+}
+
+/// Size of a camera's lighting textures, none until its target has a size. The history is kept
+/// as long as it stays the same.
+fn lighting_size(
+    camera: &ExtractedCamera,
+    resolution_scale: Option<&VordieResolutionScale>,
+) -> Option<UVec2> {
+    let physical_target_size = camera.physical_target_size?;
+    Some(
+        resolution_scale
+            .copied()
+            .unwrap_or_default()
+            .apply(physical_target_size),
+    )
+}
+
+#[allow(dead_code)]
+mod vordie_previous_view {
+    use super::*;
+
+    /// The view-projection of a camera in the last frame, the GI history is reprojected with it so
+    /// it stays in place when the camera moves.
+    #[derive(Component, Clone, Copy, ShaderType)]
+    pub struct VordiePreviousView {
+        pub clip_from_world: Mat4,
+    }
+}
+pub use vordie_previous_view::VordiePreviousView;
+
+/// Clears the GI history of a camera before its next GI pass, for cuts and teleports the
+/// automatic detection misses. Removed again once it has been extracted.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct VordieResetHistory;
+
+/// Moves `VordieResetHistory` from the main world to the render world, so it applies once.
+fn extract_reset_history(mut commands: Commands, mut main_world: ResMut<MainWorld>) {
+    let cameras: Vec<Entity> = main_world
+        .query_filtered::<Entity, With<VordieResetHistory>>()
+        .iter(&main_world)
+        .collect();
+    for entity in cameras {
+        main_world.entity_mut(entity).remove::<VordieResetHistory>();
+        commands.get_or_spawn(entity).insert(VordieResetHistory);
+    }
+}
+
+/// The history is dropped when a screen corner moves further than this between two frames, in
+/// NDC where the screen is 2.0 across.
+const CAMERA_CUT_THRESHOLD: f32 = 1.0;
+
+/// Whether the view changed too much since `previous` for its history to be reprojected.
+fn is_camera_cut(previous: Mat4, current: Mat4) -> bool {
+    let previous_from_current = previous * current.inverse();
+    [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .into_iter()
+        .any(|(x, y)| {
+            let corner = Vec3::new(x, y, 0.0);
+            let moved = previous_from_current.project_point3(corner);
+            moved.truncate().distance(corner.truncate()) > CAMERA_CUT_THRESHOLD
+        })
+}
+
+/// What the GI history of a camera was rendered with.
+struct VordieViewHistory {
+    clip_from_world: Mat4,
+    size: UVec2,
+}
+
+/// The view of each camera in the frame before, by render world entity.
+#[derive(Resource, Default)]
+struct VordiePreviousViews(EntityHashMap<VordieViewHistory>);
+
+#[allow(clippy::type_complexity)]
+fn prepare_previous_views(
+    mut commands: Commands,
+    mut previous_views: ResMut<VordiePreviousViews>,
+    views: Query<
+        (
+            Entity,
+            &ExtractedView,
+            &ExtractedCamera,
+            Option<&VordieResolutionScale>,
+            Has<VordieResetHistory>,
+        ),
+        With<VordieLightSettings>,
+    >,
+) {
+    let mut current_views = EntityHashMap::default();
+    for (entity, view, camera, resolution_scale, reset_history) in &views {
+        // Without a target there are no history textures either.
+        let Some(size) = lighting_size(camera, resolution_scale) else {
+            continue;
+        };
+        let clip_from_world = view.clip_from_world.unwrap_or_else(|| {
+            view.clip_from_view * view.world_from_view.compute_matrix().inverse()
+        });
+
+        // A new camera or a resize gets history textures from the texture cache with anything
+        // in them, and a cut has nothing on screen in common with the last frame.
+        let previous = previous_views.0.get(&entity).filter(|previous| {
+            previous.size == size && !is_camera_cut(previous.clip_from_world, clip_from_world)
+        });
+        if previous.is_none() && !reset_history {
+            commands.entity(entity).insert(VordieResetHistory);
         }
+        // Without history there is nothing to reproject, its own view keeps it in place.
+        commands.entity(entity).insert(VordiePreviousView {
+            clip_from_world: match previous {
+                Some(previous) if !reset_history => previous.clip_from_world,
+                _ => clip_from_world,
+            },
+        });
+
+        current_views.insert(
+            entity,
+            VordieViewHistory {
+                clip_from_world,
+                size,
+            },
+        );
     }
+    // Cameras that went away are dropped.
+    previous_views.0 = current_views;
 }
 
 #[derive(Component)]
 pub struct GlobalIHistoryTextures {
     write: CachedTexture,
     read: CachedTexture,
+    // Luminance mean, mean of squares, variance and history length, for the denoiser.
+    moments_write: CachedTexture,
+    moments_read: CachedTexture,
 }
 
 fn prepare_gi_history_textures(
@@ -61,25 +427,26 @@ fn prepare_gi_history_textures(
     mut texture_cache: ResMut<TextureCache>,
     render_device: Res<RenderDevice>,
     frame_count: Res<FrameCount>,
-    views: Query<(Entity, &ExtractedCamera, &ExtractedView), With<VordieLightSettings>>,
+    views: Query<
+        (Entity, &ExtractedCamera, Option<&VordieResolutionScale>),
+        With<VordieLightSettings>,
+    >,
 ) {
-    for (entity, camera, view) in &views {
-        if let Some(physical_target_size) = camera.physical_target_size {
+    for (entity, camera, resolution_scale) in &views {
+        // The history is written by the denoise pass, at the lighting resolution.
+        if let Some(size) = lighting_size(camera, resolution_scale) {
             let mut texture_descriptor = TextureDescriptor {
                 label: None,
                 size: Extent3d {
                     depth_or_array_layers: 1,
-                    width: physical_target_size.x,
-                    height: physical_target_size.y,
+                    width: size.x,
+                    height: size.y,
                 },
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
-                format: if view.hdr {
-                    ViewTarget::TEXTURE_FORMAT_HDR
-                } else {
-                    TextureFormat::bevy_default()
-                },
+                // The history holds linear radiance, whatever the view target format is.
+                format: ViewTarget::TEXTURE_FORMAT_HDR,
                 usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
                 view_formats: &[],
             };
@@ -88,17 +455,27 @@ fn prepare_gi_history_textures(
             let history_1_texture = texture_cache.get(&render_device, texture_descriptor.clone());
 
             texture_descriptor.label = Some("gi_history_2_texture");
-            let history_2_texture = texture_cache.get(&render_device, texture_descriptor);
+            let history_2_texture = texture_cache.get(&render_device, texture_descriptor.clone());
+
+            texture_descriptor.label = Some("gi_moments_1_texture");
+            let moments_1_texture = texture_cache.get(&render_device, texture_descriptor.clone());
+
+            texture_descriptor.label = Some("gi_moments_2_texture");
+            let moments_2_texture = texture_cache.get(&render_device, texture_descriptor);
 
-            let textures = if frame_count.0 % 2 == 0 {
+            let textures = if frame_count.0.is_multiple_of(2) {
                 GlobalIHistoryTextures {
                     write: history_1_texture,
                     read: history_2_texture,
+                    moments_write: moments_1_texture,
+                    moments_read: moments_2_texture,
                 }
             } else {
                 GlobalIHistoryTextures {
                     write: history_2_texture,
                     read: history_1_texture,
+                    moments_write: moments_2_texture,
+                    moments_read: moments_1_texture,
                 }
             };
 
@@ -107,47 +484,266 @@ fn prepare_gi_history_textures(
     }
 }
 
-#[derive(Component, Default, Clone, Copy, ExtractComponent, ShaderType)]
-pub struct Params {
-    pub screen_pixel_size: Vec2,
-    pub offset: f32,
+/// Which kind of passes build the distance field.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum VordieJfaBackend {
+    /// Fullscreen fragment passes.
+    #[default]
+    Fragment,
+    /// Compute dispatches on storage textures, gives the same result as `Fragment`.
+    Compute,
+}
+
+/// Precision of the jump flood seeds and the distance field.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum VordieJfaPrecision {
+    /// 16 bit floats, the seeds lose pixel accuracy on targets wider than about 2048 pixels.
+    #[default]
+    Half,
+    /// 32 bit floats, exact nearest seeds on large and high DPI targets.
+    Full,
+}
+impl VordieJfaPrecision {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::Half => TextureFormat::Rgba16Float,
+            Self::Full => TextureFormat::Rgba32Float,
+        }
+    }
+}
+
+/// Intermediate textures of the light pass, nodes added between the [`VordieNode2d`] stages
+/// can read or edit them.
+#[derive(Component)]
+pub struct VordieViewTextures {
+    /// Emitter colors, occluders are black. Alpha marks a surface.
+    pub emitters_occluders: CachedTexture,
+    /// Per channel transmittance of the occluders.
+    pub transmittance: CachedTexture,
+    /// Index of each emitter's falloff in the emitters buffer.
+    pub emitter_ids: CachedTexture,
+    /// Surface color of the occluders, the share of light they bounce. Black on emitters.
+    pub albedo: CachedTexture,
+    /// Jump flood seeds, the UV of each surface pixel and a validity flag in blue.
+    pub seed: CachedTexture,
+    /// Jump flood result, the nearest surface of each pixel.
+    pub voronoi: CachedTexture,
+    /// Seeds of the empty pixels, for the distance from inside a surface.
+    pub inverse_seed: CachedTexture,
+    /// Jump flood result of `inverse_seed`, the nearest empty pixel of each pixel.
+    pub inverse_voronoi: CachedTexture,
+    /// Jump flood passes alternate between this and `voronoi` or `inverse_voronoi`.
+    pub jfa_ping_pong: CachedTexture,
+    /// Signed distance to the nearest surface edge, negative inside the surfaces.
+    pub distance_field: CachedTexture,
+    /// GI before denoising.
+    pub gi: CachedTexture,
+    /// Light around the surfaces after each bounce, read by the next one.
+    pub surface_radiance: CachedTexture,
+    /// Bounces alternate between this and `surface_radiance`.
+    pub surface_radiance_ping_pong: CachedTexture,
+    /// Radiance cascades with [`VordieGiMode::RadianceCascades`], cascade 0 ends up here.
+    pub radiance_cascades: CachedTexture,
+    /// Radiance cascades alternate between this and `radiance_cascades`.
+    pub radiance_cascades_ping_pong: CachedTexture,
+    /// Denoised GI.
+    pub light: CachedTexture,
+    /// À-trous passes of the denoiser alternate between this and `light`.
+    pub denoise_ping_pong: CachedTexture,
+    /// Denoised GI at the view size, multiplied onto the scene colors.
+    pub upsampled_light: CachedTexture,
+}
+
+fn prepare_vordie_view_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    jfa_backend: Res<VordieJfaBackend>,
+    jfa_precision: Res<VordieJfaPrecision>,
+    views: Query<
+        (Entity, &ExtractedCamera, Option<&VordieResolutionScale>),
+        With<VordieLightSettings>,
+    >,
+) {
+    // The compute backend writes the jump flood textures as storage textures.
+    let jfa_usage = match *jfa_backend {
+        VordieJfaBackend::Fragment => {
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING
+        }
+        VordieJfaBackend::Compute => {
+            TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING
+        }
+    };
+
+    for (entity, camera, resolution_scale) in &views {
+        let (Some(physical_target_size), Some(lighting_size)) = (
+            camera.physical_target_size,
+            lighting_size(camera, resolution_scale),
+        ) else {
+            continue;
+        };
+
+        // The textures are reused across frames as long as the view size doesn't change.
+        let mut create_texture =
+            |descriptor: TextureDescriptor<'static>| texture_cache.get(&render_device, descriptor);
+
+        // Everything up to the upsample is drawn at the lighting resolution.
+        let scene_descriptor = TextureDescriptor {
+            label: Some("emitters_occluders_texture"),
+            size: Extent3d {
+                width: lighting_size.x,
+                height: lighting_size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba16Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+        let jfa_descriptor = TextureDescriptor {
+            label: Some("jfa_seed_texture"),
+            format: jfa_precision.texture_format(),
+            usage: jfa_usage,
+            ..scene_descriptor
+        };
+        let light_descriptor = TextureDescriptor {
+            label: Some("distance_field_texture"),
+            ..scene_descriptor
+        };
+
+        commands.entity(entity).insert(VordieViewTextures {
+            emitters_occluders: create_texture(scene_descriptor.clone()),
+            transmittance: create_texture(TextureDescriptor {
+                label: Some("occluders_transmittance_texture"),
+                ..scene_descriptor.clone()
+            }),
+            emitter_ids: create_texture(TextureDescriptor {
+                label: Some("emitter_ids_texture"),
+                format: TextureFormat::R32Uint,
+                ..scene_descriptor.clone()
+            }),
+            albedo: create_texture(TextureDescriptor {
+                label: Some("occluders_albedo_texture"),
+                ..scene_descriptor
+            }),
+            seed: create_texture(jfa_descriptor.clone()),
+            jfa_ping_pong: create_texture(TextureDescriptor {
+                label: Some("jfa_ping_pong_texture"),
+                ..jfa_descriptor.clone()
+            }),
+            voronoi: create_texture(TextureDescriptor {
+                label: Some("jfa_voronoi_texture"),
+                ..jfa_descriptor.clone()
+            }),
+            inverse_seed: create_texture(TextureDescriptor {
+                label: Some("jfa_inverse_seed_texture"),
+                ..jfa_descriptor.clone()
+            }),
+            inverse_voronoi: create_texture(TextureDescriptor {
+                label: Some("jfa_inverse_voronoi_texture"),
+                ..jfa_descriptor
+            }),
+            distance_field: create_texture(TextureDescriptor {
+                format: jfa_precision.texture_format(),
+                usage: jfa_usage,
+                ..light_descriptor.clone()
+            }),
+            gi: create_texture(TextureDescriptor {
+                label: Some("gi_raycast_texture"),
+                ..light_descriptor.clone()
+            }),
+            surface_radiance: create_texture(TextureDescriptor {
+                label: Some("surface_radiance_texture"),
+                ..light_descriptor.clone()
+            }),
+            surface_radiance_ping_pong: create_texture(TextureDescriptor {
+                label: Some("surface_radiance_ping_pong_texture"),
+                ..light_descriptor.clone()
+            }),
+            radiance_cascades: create_texture(TextureDescriptor {
+                label: Some("radiance_cascades_texture"),
+                ..light_descriptor.clone()
+            }),
+            radiance_cascades_ping_pong: create_texture(TextureDescriptor {
+                label: Some("radiance_cascades_ping_pong_texture"),
+                ..light_descriptor.clone()
+            }),
+            light: create_texture(TextureDescriptor {
+                label: Some("light_texture"),
+                ..light_descriptor.clone()
+            }),
+            denoise_ping_pong: create_texture(TextureDescriptor {
+                label: Some("denoise_ping_pong_texture"),
+                ..light_descriptor.clone()
+            }),
+            upsampled_light: create_texture(TextureDescriptor {
+                label: Some("upsampled_light_texture"),
+                size: Extent3d {
+                    width: physical_target_size.x,
+                    height: physical_target_size.y,
+                    depth_or_array_layers: 1,
+                },
+                ..light_descriptor
+            }),
+        });
+    }
+}
+
+#[allow(dead_code)]
+mod params {
+    use super::*;
+
+    #[derive(Component, Default, Clone, Copy, ExtractComponent, ShaderType)]
+    pub struct Params {
+        pub screen_pixel_size: Vec2,
+        pub offset: f32,
+    }
 }
+pub use params::Params;
 
 #[derive(Resource)]
 struct VordieLightPipeline {
     sampler: Sampler,
-    emiters_occs_bind_group_layout: BindGroupLayout,
     seed_bind_group_layout: BindGroupLayout,
     jfa_bind_group_layout: BindGroupLayout,
     dis_field_bind_group_layout: BindGroupLayout,
     gi_raycast_bind_group_layout: BindGroupLayout,
     denoise_bind_group_layout: BindGroupLayout,
+    atrous_bind_group_layout: BindGroupLayout,
+    upsample_bind_group_layout: BindGroupLayout,
+    composite_bind_group_layout: BindGroupLayout,
+    debug_bind_group_layout: BindGroupLayout,
+    jfa_compute_bind_group_layout: BindGroupLayout,
 
-    emiters_occs_pipeline_id: CachedRenderPipelineId,
     seed_pipeline_id: CachedRenderPipelineId,
+    inverse_seed_pipeline_id: CachedRenderPipelineId,
     jfa_pipeline_id: CachedRenderPipelineId,
     dis_field_pipeline_id: CachedRenderPipelineId,
     gi_raycast_pipeline_id: CachedRenderPipelineId,
+    radiance_cascades_pipeline_id: CachedRenderPipelineId,
     denoise_pipeline_id: CachedRenderPipelineId,
+    atrous_pipeline_id: CachedRenderPipelineId,
+    upsample_pipeline_id: CachedRenderPipelineId,
+    // The composite pass writes to the view target, so it is specialized per view.
+    composite_shader: Handle<Shader>,
+    debug_shader: Handle<Shader>,
+    // Read by the GI pass with `VordieRaySampling::BlueNoise`.
+    blue_noise: Handle<Image>,
+
+    // Only queued with the compute backend, so WebGL2 never needs compute support.
+    jfa_compute_seed_pipeline_id: Option<CachedComputePipelineId>,
+    jfa_compute_inverse_seed_pipeline_id: Option<CachedComputePipelineId>,
+    jfa_compute_pipeline_id: Option<CachedComputePipelineId>,
+    jfa_compute_dis_field_pipeline_id: Option<CachedComputePipelineId>,
 }
 
 impl FromWorld for VordieLightPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.get_resource::<RenderDevice>().unwrap().clone();
+        let jfa_precision = *world.resource::<VordieJfaPrecision>();
 
-        let emiters_occs_bind_group_layout = render_device.create_bind_group_layout(
-            "vordie_light_emiters_occs_group_layout",
-            &BindGroupLayoutEntries::sequential(
-                // The layout entries will only be visible in the fragment stage
-                ShaderStages::FRAGMENT,
-                (
-                    // The screen texture
-                    texture_2d(TextureSampleType::Float { filterable: false }),
-                    // The sampler that will be used to sample the screen texture
-                    sampler(SamplerBindingType::NonFiltering),
-                ),
-            ),
-        );
         let seed_bind_group_layout = render_device.create_bind_group_layout(
             "vordie_light_init_group_layout",
             &BindGroupLayoutEntries::sequential(
@@ -159,7 +755,7 @@ impl FromWorld for VordieLightPipeline {
                     // The sampler that will be used to sample the screen texture
                     sampler(SamplerBindingType::NonFiltering),
                     // The settings uniform that will control the effect
-                    uniform_buffer::<VordieLightSettings>(false),
+                    uniform_buffer::<VordieLightSettings>(true),
                 ),
             ),
         );
@@ -174,7 +770,7 @@ impl FromWorld for VordieLightPipeline {
                     // The sampler that will be used to sample the screen texture
                     sampler(SamplerBindingType::NonFiltering),
                     // The settings uniform that will control the effect
-                    uniform_buffer::<VordieLightSettings>(false),
+                    uniform_buffer::<VordieLightSettings>(true),
                     // Jumpflood params
                     uniform_buffer::<Params>(false),
                 ),
@@ -191,7 +787,9 @@ impl FromWorld for VordieLightPipeline {
                     // The sampler that will be used to sample the screen texture
                     sampler(SamplerBindingType::NonFiltering),
                     // The settings uniform that will control the effect
-                    uniform_buffer::<VordieLightSettings>(false),
+                    uniform_buffer::<VordieLightSettings>(true),
+                    // The jump flood result of the inverted mask
+                    texture_2d(TextureSampleType::Float { filterable: false }),
                 ),
             ),
         );
@@ -206,13 +804,35 @@ impl FromWorld for VordieLightPipeline {
                     // The sampler that will be used to sample the screen texture
                     sampler(SamplerBindingType::NonFiltering),
                     // The settings uniform that will control the effect
-                    uniform_buffer::<VordieLightSettings>(false),
+                    uniform_buffer::<VordieLightSettings>(true),
                     // Emitter and occluder texture
                     texture_2d(TextureSampleType::Float { filterable: false }),
-                    // GI History (read)
+                    // Surface radiance of the last bounce
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Frame number and sampling strategy of the noise
+                    uniform_buffer::<VordieNoiseParams>(false),
+                    // View, to place the analytic lights on screen
+                    uniform_buffer::<ViewUniform>(true),
+                    // Spot lights
+                    storage_buffer_read_only::<GpuVordieSpotLights>(false),
+                    // Occluder transmittance texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Emitter ID texture
+                    texture_2d(TextureSampleType::Uint),
+                    // Per emitter falloff
+                    storage_buffer_read_only::<GpuVordieEmitters>(false),
+                    // Occluder albedo texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Blue noise tile
                     texture_2d(TextureSampleType::Float { filterable: false }),
-                    // Time
-                    uniform_buffer::<f32>(false),
+                    // Radiance cascade above the one drawn, or cascade 0 for the GI
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Bounce and radiance cascade drawn
+                    uniform_buffer::<VordieGiPassParams>(false),
+                    // Sky texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Sky of the rays that leave the view
+                    uniform_buffer::<VordieSkyParams>(false),
                 ),
             ),
         );
@@ -229,32 +849,139 @@ impl FromWorld for VordieLightPipeline {
                     // GI History (read)
                     texture_2d(TextureSampleType::Float { filterable: false }),
                     // The settings uniform that will control the effect
-                    uniform_buffer::<VordieLightSettings>(false),
+                    uniform_buffer::<VordieLightSettings>(true),
                     // Screen pixel size
                     uniform_buffer::<Params>(false),
+                    // GI moments (read)
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // View, to reproject the history
+                    uniform_buffer::<ViewUniform>(true),
+                    // Last frame's view
+                    uniform_buffer::<VordiePreviousView>(false),
+                ),
+            ),
+        );
+        let atrous_bind_group_layout = render_device.create_bind_group_layout(
+            "vordie_light_atrous_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                // The layout entries will only be visible in the fragment stage
+                ShaderStages::FRAGMENT,
+                (
+                    // The light of the last pass
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // GI moments of this frame, their variance guides the filter
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Emitter and occluder texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Distance field
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The settings uniform that will control the effect
+                    uniform_buffer::<VordieLightSettings>(true),
+                    // Step between the taps
+                    uniform_buffer::<Params>(false),
+                ),
+            ),
+        );
+        let upsample_bind_group_layout = render_device.create_bind_group_layout(
+            "vordie_light_upsample_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                // The layout entries will only be visible in the fragment stage
+                ShaderStages::FRAGMENT,
+                (
+                    // The denoised light
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The scene colors at the view size, guiding the upsample
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The settings uniform that will control the effect
+                    uniform_buffer::<VordieLightSettings>(true),
+                ),
+            ),
+        );
+        let composite_bind_group_layout = render_device.create_bind_group_layout(
+            "vordie_light_composite_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                // The layout entries will only be visible in the fragment stage
+                ShaderStages::FRAGMENT,
+                (
+                    // The scene texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The sampler that will be used to sample the screen texture
+                    sampler(SamplerBindingType::NonFiltering),
+                    // The denoised light
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // Emitter and occluder texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The settings uniform that will control the effect
+                    uniform_buffer::<VordieLightSettings>(true),
+                ),
+            ),
+        );
+        let debug_bind_group_layout = render_device.create_bind_group_layout(
+            "vordie_light_debug_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                // The layout entries will only be visible in the fragment stage
+                ShaderStages::FRAGMENT,
+                (
+                    // The buffer to show
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The sampler that will be used to sample the buffer
+                    sampler(SamplerBindingType::NonFiltering),
+                ),
+            ),
+        );
+        let jfa_compute_bind_group_layout = render_device.create_bind_group_layout(
+            "vordie_light_jfa_compute_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                // The layout entries will only be visible in the compute stage
+                ShaderStages::COMPUTE,
+                (
+                    // The source texture
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    // The sampler that will be used to sample the source texture
+                    sampler(SamplerBindingType::NonFiltering),
+                    // The settings uniform that will control the effect
+                    uniform_buffer::<VordieLightSettings>(true),
+                    // Jumpflood params
+                    uniform_buffer::<Params>(false),
+                    // The destination texture
+                    texture_storage_2d(
+                        jfa_precision.texture_format(),
+                        StorageTextureAccess::WriteOnly,
+                    ),
+                    // The inverse source texture, only read by the distance field pass
+                    texture_2d(TextureSampleType::Float { filterable: false }),
                 ),
             ),
         );
 
         let assets_server = world.resource::<AssetServer>();
-        let emiters_occs_shader = assets_server.load("shaders/vordie_emiters_occs.wgsl");
         let seed_shader = assets_server.load("shaders/vordie_seed.wgsl");
         let jfa_shader = assets_server.load("shaders/vordie_jfa.wgsl");
         let dis_field_shader = assets_server.load("shaders/vordie_dis_field.wgsl");
         let gi_raycast_shader = assets_server.load("shaders/vordie_gi_raycast.wgsl");
         let denoise_shader = assets_server.load("shaders/vordie_denoise.wgsl");
+        let atrous_shader = assets_server.load("shaders/vordie_atrous.wgsl");
+        let upsample_shader = assets_server.load("shaders/vordie_upsample.wgsl");
+        let composite_shader = assets_server.load("shaders/vordie_composite.wgsl");
+        let debug_shader = assets_server.load("shaders/vordie_debug.wgsl");
+        let jfa_compute_shader = assets_server.load("shaders/vordie_jfa_compute.wgsl");
+        // 64x64 void and cluster blue noise, the values are ranks and not colors.
+        let blue_noise = assets_server.load_with_settings(
+            "textures/vordie_blue_noise.png",
+            |settings: &mut ImageLoaderSettings| settings.is_srgb = false,
+        );
 
         let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
-        let emiters_occs_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
-            label: Some("vordie_emiters_occs_pipeline".into()),
-            layout: vec![emiters_occs_bind_group_layout.clone()],
+        let seed_descriptor = RenderPipelineDescriptor {
+            label: Some("vordie_seed_pipeline".into()),
+            layout: vec![seed_bind_group_layout.clone()],
             vertex: fullscreen_shader_vertex_state(),
             fragment: Some(FragmentState {
-                shader: emiters_occs_shader.clone(),
+                shader: seed_shader.clone(),
                 shader_defs: vec![],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::Rgba16Float,
+                    format: jfa_precision.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
@@ -275,37 +1002,22 @@ impl FromWorld for VordieLightPipeline {
                 alpha_to_coverage_enabled: false,
             },
             push_constant_ranges: vec![],
-        });
-        let seed_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
-            label: Some("vordie_seed_pipeline".into()),
-            layout: vec![seed_bind_group_layout.clone()],
-            vertex: fullscreen_shader_vertex_state(),
+        };
+        let seed_cached = pipeline_cache.queue_render_pipeline(seed_descriptor.clone());
+        // The same pass on the inverted mask, seeding the empty pixels.
+        let inverse_seed_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("vordie_inverse_seed_pipeline".into()),
             fragment: Some(FragmentState {
                 shader: seed_shader.clone(),
-                shader_defs: vec![],
+                shader_defs: vec!["INVERT_MASK".into()],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::Rgba16Float,
+                    format: jfa_precision.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
             }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            push_constant_ranges: vec![],
+            ..seed_descriptor
         });
         let jfa_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
             label: Some("vordie_jfa_pipeline".into()),
@@ -316,7 +1028,7 @@ impl FromWorld for VordieLightPipeline {
                 shader_defs: vec![],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::Rgba16Float,
+                    format: jfa_precision.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
@@ -347,7 +1059,7 @@ impl FromWorld for VordieLightPipeline {
                 shader_defs: vec![],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::Rgba16Float,
+                    format: jfa_precision.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
@@ -377,11 +1089,19 @@ impl FromWorld for VordieLightPipeline {
                 shader: gi_raycast_shader.clone(),
                 shader_defs: vec![],
                 entry_point: "fragment".into(),
-                targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::Rgba16Float,
-                    blend: None,
-                    write_mask: ColorWrites::ALL,
-                })],
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    // Surface radiance for the next bounce
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
             }),
             primitive: PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
@@ -400,6 +1120,39 @@ impl FromWorld for VordieLightPipeline {
             },
             push_constant_ranges: vec![],
         });
+        // Same shader and layout as the GI pass, drawing one cascade at a time.
+        let radiance_cascades_cached =
+            pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some("vordie_radiance_cascades_pipeline".into()),
+                layout: vec![gi_raycast_bind_group_layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader: gi_raycast_shader.clone(),
+                    shader_defs: vec![],
+                    entry_point: "cascade_fragment".into(),
+                    targets: vec![Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    cull_mode: Some(Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                push_constant_ranges: vec![],
+            });
         let denoise_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
             label: Some("vordie_denoise_pipeline".into()),
             layout: vec![denoise_bind_group_layout.clone()],
@@ -419,6 +1172,11 @@ impl FromWorld for VordieLightPipeline {
                         blend: None,
                         write_mask: ColorWrites::ALL,
                     }),
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
                 ],
             }),
             primitive: PrimitiveState {
@@ -439,456 +1197,287 @@ impl FromWorld for VordieLightPipeline {
             push_constant_ranges: vec![],
         });
 
+        let atrous_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("vordie_atrous_pipeline".into()),
+            layout: vec![atrous_bind_group_layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: atrous_shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            push_constant_ranges: vec![],
+        });
+
+        let upsample_cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("vordie_upsample_pipeline".into()),
+            layout: vec![upsample_bind_group_layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: upsample_shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            push_constant_ranges: vec![],
+        });
+
+        // Compute backend of the seed, inverse seed, jump flood and distance field passes, one entry
+        // point each.
+        let jfa_compute_cached =
+            (*world.resource::<VordieJfaBackend>() == VordieJfaBackend::Compute).then(|| {
+                ["seed", "inverse_seed", "jump_flood", "distance_field"].map(|entry_point| {
+                    pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                        label: Some(format!("vordie_jfa_compute_{entry_point}_pipeline").into()),
+                        layout: vec![jfa_compute_bind_group_layout.clone()],
+                        push_constant_ranges: vec![],
+                        shader: jfa_compute_shader.clone(),
+                        shader_defs: match jfa_precision {
+                            VordieJfaPrecision::Half => vec![],
+                            VordieJfaPrecision::Full => vec!["JFA_FULL_PRECISION".into()],
+                        },
+                        entry_point: entry_point.into(),
+                    })
+                })
+            });
+
         // We can create the sampler here since it won't change at runtime and doesn't depend on the view.
         let sampler = render_device.create_sampler(&SamplerDescriptor::default());
 
         Self {
             sampler,
-            emiters_occs_bind_group_layout,
             seed_bind_group_layout,
             jfa_bind_group_layout,
             dis_field_bind_group_layout,
             gi_raycast_bind_group_layout,
             denoise_bind_group_layout,
+            atrous_bind_group_layout,
+            upsample_bind_group_layout,
+            composite_bind_group_layout,
+            debug_bind_group_layout,
+            jfa_compute_bind_group_layout,
 
-            emiters_occs_pipeline_id: emiters_occs_cached,
             seed_pipeline_id: seed_cached,
+            inverse_seed_pipeline_id: inverse_seed_cached,
             jfa_pipeline_id: jfa_cached,
             dis_field_pipeline_id: dis_field_cached,
             gi_raycast_pipeline_id: gi_raycast_cached,
+            radiance_cascades_pipeline_id: radiance_cascades_cached,
             denoise_pipeline_id: denoise_cached,
+            atrous_pipeline_id: atrous_cached,
+            upsample_pipeline_id: upsample_cached,
+            composite_shader,
+            debug_shader,
+            blue_noise,
+
+            jfa_compute_seed_pipeline_id: jfa_compute_cached.map(|ids| ids[0]),
+            jfa_compute_inverse_seed_pipeline_id: jfa_compute_cached.map(|ids| ids[1]),
+            jfa_compute_pipeline_id: jfa_compute_cached.map(|ids| ids[2]),
+            jfa_compute_dis_field_pipeline_id: jfa_compute_cached.map(|ids| ids[3]),
         }
     }
 }
 
-#[derive(Default)]
-struct VordieNode;
-
-impl ViewNode for VordieNode {
-    // This query will only run on the view entity
-    type ViewQuery = (
-        &'static ViewTarget,
-        &'static GlobalIHistoryTextures,
-        // This makes sure the node only runs on cameras with the VordieLightSettings component
-        &'static VordieLightSettings,
-    );
-
-    fn run(
-        &self,
-        _graph: &mut RenderGraphContext,
-        render_context: &mut RenderContext,
-        (view_target, gi_history_textures, _vordie_light_settings): QueryItem<Self::ViewQuery>,
-        world: &World,
-    ) -> Result<(), NodeRunError> {
-        let vordie_pipeline = world.resource::<VordieLightPipeline>();
-        let pipeline_cache = world.resource::<PipelineCache>();
-
-        let Some(emiters_occs_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.emiters_occs_pipeline_id)
-        else {
-            return Ok(());
-        };
-        let Some(seed_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.seed_pipeline_id)
-        else {
-            return Ok(());
-        };
-        let Some(main_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.jfa_pipeline_id)
-        else {
-            return Ok(());
-        };
-        let Some(dis_field_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.dis_field_pipeline_id)
-        else {
-            return Ok(());
-        };
-        let Some(gi_raycast_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.gi_raycast_pipeline_id)
-        else {
-            return Ok(());
-        };
-        let Some(denoise_pipeline) =
-            pipeline_cache.get_render_pipeline(vordie_pipeline.denoise_pipeline_id)
-        else {
-            return Ok(());
-        };
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct VordieCompositePipelineKey {
+    hdr: bool,
+    debug_view: VordieDebugView,
+}
 
-        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
-        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
-            return Ok(());
-        };
+impl SpecializedRenderPipeline for VordieLightPipeline {
+    type Key = VordieCompositePipelineKey;
 
-        // Creating emitters and occluders texture
-        let emitters_occluders_descriptor = TextureDescriptor {
-            label: Some("emitters_occluders_texture"),
-            size: Extent3d {
-                width: view_target.main_texture().width() / 2,
-                height: view_target.main_texture().height() / 2,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba16Float,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        // Debug views replace the composite with a single buffer.
+        let debug_def = match key.debug_view {
+            VordieDebugView::Scene => Some("DEBUG_SCENE"),
+            VordieDebugView::Seeds | VordieDebugView::Voronoi => Some("DEBUG_UV"),
+            VordieDebugView::DistanceField => Some("DEBUG_DISTANCE_FIELD"),
+            VordieDebugView::Gi | VordieDebugView::History => Some("DEBUG_RADIANCE"),
+            VordieDebugView::Final => None,
+        };
+        let (layout, shader, shader_defs) = match debug_def {
+            Some(def) => (
+                self.debug_bind_group_layout.clone(),
+                self.debug_shader.clone(),
+                vec![def.into()],
+            ),
+            None => (
+                self.composite_bind_group_layout.clone(),
+                self.composite_shader.clone(),
+                vec![],
+            ),
         };
-        let emitters_occluders_view = render_context
-            .render_device()
-            .create_texture(&emitters_occluders_descriptor)
-            .create_view(&TextureViewDescriptor {
-                ..Default::default()
-            });
-        {
-            let view_texture = view_target.main_texture_view();
-
-            let bind_group = render_context.render_device().create_bind_group(
-                "emitters_occluders_bind_group",
-                &vordie_pipeline.emiters_occs_bind_group_layout,
-                &BindGroupEntries::sequential((
-                    view_texture,
-                    // Use the sampler created for the pipeline
-                    &vordie_pipeline.sampler,
-                )),
-            );
-            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                label: Some("emitters_occluders"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &emitters_occluders_view,
-                    resolve_target: None,
-                    ops: Operations::default(),
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_render_pipeline(emiters_occs_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
-        }
-
-        if STEP == 0 {
-            return Ok(());
-        }
-
-        // Initialize the jump flood algorithm
-        {
-            let view_texture = view_target.post_process_write();
-
-            let bind_group = render_context.render_device().create_bind_group(
-                "post_process_bind_group",
-                &vordie_pipeline.seed_bind_group_layout,
-                &BindGroupEntries::sequential((
-                    // Make sure to use the source view
-                    view_texture.source,
-                    // Use the sampler created for the pipeline
-                    &vordie_pipeline.sampler,
-                    // Set the settings binding, including the offset
-                    settings_binding.clone(),
-                )),
-            );
-            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                label: Some("vordie_light_init"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: view_texture.destination,
-                    resolve_target: None,
-                    ops: Operations::default(),
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_render_pipeline(seed_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
-        }
-
-        if STEP == 1 {
-            return Ok(());
-        }
-
-        // Begining the jump flood algorithm loop
-        // Buffer for params in the jumpflood algorithm
-        let render_device = world.get_resource::<RenderDevice>().unwrap().clone();
-        let render_queue = world.resource::<RenderQueue>();
-        {
-            let prev_texture_descriptor = TextureDescriptor {
-                label: Some("jfa_source_texture"),
-                size: Extent3d {
-                    width: view_target.main_texture().width() / 2,
-                    height: view_target.main_texture().width() / 2,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba16Float,
-                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            };
-            let mut prev_view = render_context
-                .render_device()
-                .create_texture(&prev_texture_descriptor)
-                .create_view(&TextureViewDescriptor {
-                    ..Default::default()
-                });
-
-            let screen_size = Vec2::new(
-                (view_target.main_texture().width() / 2) as f32,
-                (view_target.main_texture().height() / 2) as f32,
-            );
 
-            let passes = f32::max(screen_size.x, screen_size.y).log2().ceil() as i32;
-            // print!("passes: {}", passes);
-            // let passes = 10;
-            let stop_at = 50;
-
-            for i in 0..=passes {
-                // Create the destination textures
-                let destination_texture_descriptor = TextureDescriptor {
-                    label: Some("jfa_destination_texture"),
-                    size: Extent3d {
-                        width: view_target.main_texture().width() / 2,
-                        height: view_target.main_texture().width() / 2,
-                        depth_or_array_layers: 1,
+        RenderPipelineDescriptor {
+            label: Some("vordie_composite_pipeline".into()),
+            layout: vec![layout],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader,
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
                     },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: TextureDimension::D2,
-                    format: TextureFormat::Rgba16Float,
-                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-                    view_formats: &[],
-                };
-                let destination_view = render_context
-                    .render_device()
-                    .create_texture(&destination_texture_descriptor)
-                    .create_view(&TextureViewDescriptor {
-                        ..Default::default()
-                    });
-
-                let source = if i == 0 {
-                    view_target.main_texture_view().clone()
-                } else {
-                    prev_view.clone()
-                };
-
-                let offset = 2f32.powi(passes - i - 1);
-
-                let mut params_buffer = UniformBuffer::<Params>::from(Params {
-                    screen_pixel_size: screen_size,
-                    offset,
-                });
-                params_buffer.write_buffer(&render_device, render_queue);
-
-                let bind_group = render_context.render_device().create_bind_group(
-                    "post_process_bind_group",
-                    &vordie_pipeline.jfa_bind_group_layout,
-                    &BindGroupEntries::sequential((
-                        // Make sure to use the source view
-                        &source,
-                        // Use the sampler created for the pipeline
-                        &vordie_pipeline.sampler,
-                        // Set the settings binding, including the offset
-                        settings_binding.clone(),
-                        // Create new params binding
-                        params_buffer.binding().unwrap(),
-                    )),
-                );
-
-                let color_attachment = if i == passes || i == stop_at {
-                    view_target.get_unsampled_color_attachment()
-                } else {
-                    RenderPassColorAttachment {
-                        view: &destination_view,
-                        resolve_target: None,
-                        ops: Operations::default(),
-                    }
-                };
-                let mut render_pass =
-                    render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                        label: Some("vordie_light_init"),
-                        color_attachments: &[Some(color_attachment)],
-                        depth_stencil_attachment: None,
-                        timestamp_writes: None,
-                        occlusion_query_set: None,
-                    });
-                render_pass.set_render_pipeline(main_pipeline);
-                render_pass.set_bind_group(0, &bind_group, &[]);
-                render_pass.draw(0..3, 0..1);
-
-                // Set the target for the next iteration
-                prev_view = destination_view.clone();
-
-                if i == stop_at {
-                    break;
-                }
-            }
-        }
-
-        if STEP == 2 {
-            return Ok(());
-        }
-
-        // Distance Field Pass
-        {
-            let view_texture = view_target.post_process_write();
-
-            let bind_group = render_context.render_device().create_bind_group(
-                "dis_field_bind_group",
-                &vordie_pipeline.dis_field_bind_group_layout,
-                &BindGroupEntries::sequential((
-                    // Make sure to use the source view
-                    view_texture.source,
-                    // Use the sampler created for the pipeline
-                    &vordie_pipeline.sampler,
-                    // Set the settings binding, including the offset
-                    settings_binding.clone(),
-                )),
-            );
-            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                label: Some("vordie_light_init"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: view_texture.destination,
-                    resolve_target: None,
-                    ops: Operations::default(),
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
                 })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_render_pipeline(dis_field_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            push_constant_ranges: vec![],
         }
+    }
+}
 
-        if STEP == 3 {
-            return Ok(());
-        }
+#[derive(Component)]
+struct VordieCompositePipelineId(CachedRenderPipelineId);
 
-        // GI Raycast Pass
-        {
-            let start1 = std::time::SystemTime::now();
-            let since_the_epoch1 = start1
-                .duration_since(std::time::UNIX_EPOCH)
-                .expect("Time went backwards");
-            let mut time_buffer = UniformBuffer::<f32>::from(
-                since_the_epoch1
-                    .as_millis()
-                    .to_string()
-                    .chars()
-                    .last()
-                    .unwrap()
-                    .to_digit(10)
-                    .unwrap() as f32
-                    + 1.0 / 5.0,
-            );
-            time_buffer.write_buffer(&render_device, render_queue);
-
-            let view_texture = view_target.post_process_write();
-
-            let bind_group = render_context.render_device().create_bind_group(
-                "gi_raycast_bind_group",
-                &vordie_pipeline.gi_raycast_bind_group_layout,
-                &BindGroupEntries::sequential((
-                    // Make sure to use the source view
-                    view_texture.source,
-                    // Use the sampler created for the pipeline
-                    &vordie_pipeline.sampler,
-                    // Set the settings binding, including the offset
-                    settings_binding.clone(),
-                    // Set the emitters and occluders texture
-                    &emitters_occluders_view,
-                    // Past frames
-                    &gi_history_textures.read.default_view,
-                    // Set the time
-                    time_buffer.binding().unwrap(),
-                )),
-            );
-            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                label: Some("vordie_light_init"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: view_texture.destination,
-                    resolve_target: None,
-                    ops: Operations::default(),
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_render_pipeline(gi_raycast_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
-        }
+fn prepare_vordie_composite_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<VordieLightPipeline>>,
+    vordie_pipeline: Res<VordieLightPipeline>,
+    views: Query<(Entity, &ExtractedView, Option<&VordieDebugView>), With<VordieLightSettings>>,
+) {
+    for (entity, view, debug_view) in &views {
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &vordie_pipeline,
+            VordieCompositePipelineKey {
+                hdr: view.hdr,
+                debug_view: debug_view.copied().unwrap_or_default(),
+            },
+        );
+        commands
+            .entity(entity)
+            .insert(VordieCompositePipelineId(pipeline_id));
+    }
+}
 
-        if STEP == 4 {
-            return Ok(());
+/// Where the light pass runs in the 2D render graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VordieLightPassOrder {
+    /// On the linear HDR scene, before bloom and tonemapping.
+    /// Needs an HDR camera for the light to go above 1.0.
+    BeforeTonemapping,
+    /// On the linear HDR scene after bloom, so the light itself doesn't bloom.
+    AfterBloom,
+    /// On the tonemapped image.
+    #[default]
+    AfterTonemapping,
+    /// Between two nodes of the `Core2d` graph.
+    Custom {
+        after: InternedRenderLabel,
+        before: InternedRenderLabel,
+    },
+}
+impl VordieLightPassOrder {
+    /// Runs the light pass after the `after` node and before the `before` node.
+    pub fn custom(after: impl RenderLabel, before: impl RenderLabel) -> Self {
+        Self::Custom {
+            after: after.intern(),
+            before: before.intern(),
         }
+    }
+}
 
-        // Denoise Pass
-        {
-            let view_texture = view_target.post_process_write();
-
-            let mut params_buffer = UniformBuffer::<Params>::from(Params {
-                screen_pixel_size: Vec2::new(
-                    (view_target.main_texture().width() / 2) as f32,
-                    (view_target.main_texture().height() / 2) as f32,
-                ),
-                offset: 0.0,
-            });
-            params_buffer.write_buffer(&render_device, render_queue);
-
-            let bind_group = render_context.render_device().create_bind_group(
-                "denoise_bind_group",
-                &vordie_pipeline.denoise_bind_group_layout,
-                &BindGroupEntries::sequential((
-                    // Make sure to use the source view
-                    view_texture.source,
-                    // Use the sampler created for the pipeline
-                    &vordie_pipeline.sampler,
-                    // Past frames
-                    &gi_history_textures.read.default_view,
-                    // Set the settings binding, including the offset
-                    settings_binding.clone(),
-                    // Set the params binding
-                    params_buffer.binding().unwrap(),
-                )),
-            );
-            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-                label: Some("vordie_light_init"),
-                color_attachments: &[
-                    Some(RenderPassColorAttachment {
-                        view: view_texture.destination,
-                        resolve_target: None,
-                        ops: Operations::default(),
-                    }),
-                    Some(RenderPassColorAttachment {
-                        view: &gi_history_textures.write.default_view,
-                        resolve_target: None,
-                        ops: Operations::default(),
-                    }),
-                ],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_render_pipeline(denoise_pipeline);
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+pub struct VordieLight2DPlugin {
+    /// Where the light pass runs in the 2D render graph.
+    pub order: VordieLightPassOrder,
+    /// Draw meshes and sprites marked with `VordieEmitter` or `VordieOccluder`.
+    pub emitters_and_occluders: bool,
+    /// Extract `VordiePointLight`s.
+    pub point_lights: bool,
+    /// Extract `VordieSpotLight`s.
+    pub spot_lights: bool,
+    /// Runs the seed, jump flood and distance field passes as fragment or compute passes.
+    pub jfa_backend: VordieJfaBackend,
+    /// Stores the jump flood seeds and distance field as 16 or 32 bit floats.
+    pub jfa_precision: VordieJfaPrecision,
+}
+impl Default for VordieLight2DPlugin {
+    fn default() -> Self {
+        Self {
+            order: VordieLightPassOrder::default(),
+            emitters_and_occluders: true,
+            point_lights: true,
+            spot_lights: true,
+            jfa_backend: VordieJfaBackend::default(),
+            jfa_precision: VordieJfaPrecision::default(),
         }
-
-        Ok(())
     }
 }
 
-pub struct VordieLight2DPlugin;
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
-pub struct LightPass2DRenderLabel;
-
 impl Plugin for VordieLight2DPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
+            // Draws entities marked with `VordieEmitter` or `VordieOccluder` into their own
+            // render phase, which is used as the emitter and occluder texture.
+            scene::VordieScenePlugin {
+                emitters_and_occluders: self.emitters_and_occluders,
+            },
+            // Extracts the analytic lights, which need no geometry on screen.
+            lights::VordieLightsPlugin {
+                point_lights: self.point_lights,
+                spot_lights: self.spot_lights,
+            },
             // The settings will be a component that lives in the main world but will
             // be extracted to the render world every frame.
             // This makes it possible to control the effect from the main world.
@@ -898,6 +1487,12 @@ impl Plugin for VordieLight2DPlugin {
             // This plugin will prepare the component for the GPU by creating a uniform buffer
             // and writing the data to that buffer every frame.
             UniformComponentPlugin::<VordieLightSettings>::default(),
+            // Picks the buffer shown on each camera at runtime.
+            ExtractComponentPlugin::<VordieDebugView>::default(),
+            ExtractComponentPlugin::<VordieResolutionScale>::default(),
+            ExtractComponentPlugin::<VordieRaySampling>::default(),
+            ExtractComponentPlugin::<VordieGiMode>::default(),
+            ExtractComponentPlugin::<VordieSky>::default(),
         ));
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -905,21 +1500,77 @@ impl Plugin for VordieLight2DPlugin {
         };
 
         render_app
+            .insert_resource(self.jfa_backend)
+            .insert_resource(self.jfa_precision)
+            .init_resource::<SpecializedRenderPipelines<VordieLightPipeline>>()
+            .init_resource::<VordiePreviousViews>()
+            .add_systems(ExtractSchedule, extract_reset_history)
             .add_systems(
                 Render,
-                prepare_gi_history_textures.in_set(RenderSet::PrepareResources),
-            )
-            .add_render_graph_node::<ViewNodeRunner<VordieNode>>(Core2d, LightPass2DRenderLabel)
-            .add_render_graph_edges(
-                Core2d,
-                // Specify the node ordering.
-                // This will automatically create all required node edges to enforce the given ordering.
                 (
-                    Node2d::Tonemapping,
-                    LightPass2DRenderLabel,
-                    Node2d::EndMainPassPostProcessing,
+                    prepare_gi_history_textures.in_set(RenderSet::PrepareResources),
+                    prepare_previous_views.in_set(RenderSet::PrepareResources),
+                    prepare_vordie_view_textures.in_set(RenderSet::PrepareResources),
+                    prepare_vordie_composite_pipelines.in_set(RenderSet::Prepare),
                 ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieSceneNode>>(Core2d, VordieNode2d::Scene)
+            .add_render_graph_node::<ViewNodeRunner<VordieSeedNode>>(Core2d, VordieNode2d::Seed)
+            .add_render_graph_node::<ViewNodeRunner<VordieJumpFloodNode>>(
+                Core2d,
+                VordieNode2d::JumpFlood,
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieDistanceFieldNode>>(
+                Core2d,
+                VordieNode2d::DistanceField,
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieGiRaycastNode>>(
+                Core2d,
+                VordieNode2d::GiRaycast,
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieDenoiseNode>>(
+                Core2d,
+                VordieNode2d::Denoise,
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieUpsampleNode>>(
+                Core2d,
+                VordieNode2d::Upsample,
+            )
+            .add_render_graph_node::<ViewNodeRunner<VordieCompositeNode>>(
+                Core2d,
+                VordieNode2d::Composite,
             );
+
+        let (after, before) = match self.order {
+            VordieLightPassOrder::BeforeTonemapping => {
+                (Node2d::EndMainPass.intern(), Node2d::Bloom.intern())
+            }
+            VordieLightPassOrder::AfterBloom => {
+                (Node2d::Bloom.intern(), Node2d::Tonemapping.intern())
+            }
+            VordieLightPassOrder::AfterTonemapping => (
+                Node2d::Tonemapping.intern(),
+                Node2d::EndMainPassPostProcessing.intern(),
+            ),
+            VordieLightPassOrder::Custom { after, before } => (after, before),
+        };
+        render_app.add_render_graph_edges(
+            Core2d,
+            // Specify the node ordering.
+            // This will automatically create all required node edges to enforce the given ordering.
+            (
+                after,
+                VordieNode2d::Scene,
+                VordieNode2d::Seed,
+                VordieNode2d::JumpFlood,
+                VordieNode2d::DistanceField,
+                VordieNode2d::GiRaycast,
+                VordieNode2d::Denoise,
+                VordieNode2d::Upsample,
+                VordieNode2d::Composite,
+                before,
+            ),
+        );
     }
 
     fn finish(&self, app: &mut App) {
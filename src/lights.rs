@@ -0,0 +1,243 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{ShaderType, StorageBuffer},
+        renderer::{RenderDevice, RenderQueue},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::scene::{clear_vordie_scene, ExtractedVordieScene};
+
+/// A light that needs no mesh or sprite on screen.
+///
+/// It is splatted into the emitter texture as a disc of `radius` world units
+/// around the entity's position, before the jump flood seed pass.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VordiePointLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32,
+}
+impl Default for VordiePointLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.0,
+            radius: 5.0,
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct VordiePointLightBundle {
+    pub point_light: VordiePointLight,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+}
+
+/// A cone of light, evaluated analytically in the GI raycast pass.
+///
+/// Only pixels inside the cone and within its range gather its energy,
+/// and a shadow ray is marched towards the light for each of them.
+/// `direction` is in the entity's local space, so the cone follows its rotation.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VordieSpotLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub direction: Vec2,
+    /// Angle from `direction`, in radians, inside which the light is at full strength.
+    pub inner_angle: f32,
+    /// Angle from `direction`, in radians, outside of which the light has no effect.
+    pub outer_angle: f32,
+    /// How far the light reaches in world units, `None` uses the camera's `u_emission_range`
+    /// measured on screen like an emitter's.
+    pub range: Option<f32>,
+    /// How fast the light fades over its range, `None` uses the camera's `u_emission_dropoff`.
+    pub dropoff: Option<f32>,
+}
+impl Default for VordieSpotLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.0,
+            direction: Vec2::X,
+            inner_angle: std::f32::consts::PI / 8.0,
+            outer_angle: std::f32::consts::PI / 6.0,
+            range: Some(200.0),
+            dropoff: None,
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct VordieSpotLightBundle {
+    pub spot_light: VordieSpotLight,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+}
+
+// In its own module to scope the dead code allow, see `VordieLightSettings`.
+#[allow(dead_code)]
+mod gpu_vordie_spot_light {
+    use super::*;
+
+    /// Negative range and dropoff fall back to the camera's settings, like `GpuVordieEmitter`.
+    #[derive(Clone, Default, ShaderType)]
+    pub(crate) struct GpuVordieSpotLight {
+        pub(super) color: Vec4,
+        pub(super) position: Vec2,
+        pub(super) direction: Vec2,
+        pub(super) cos_inner: f32,
+        pub(super) cos_outer: f32,
+        pub(super) range: f32,
+        pub(super) dropoff: f32,
+    }
+}
+pub(crate) use gpu_vordie_spot_light::GpuVordieSpotLight;
+
+#[allow(dead_code)]
+mod gpu_vordie_spot_lights {
+    use super::*;
+
+    #[derive(Default, ShaderType)]
+    pub(crate) struct GpuVordieSpotLights {
+        pub(super) count: u32,
+        #[size(runtime)]
+        pub(super) data: Vec<GpuVordieSpotLight>,
+    }
+}
+pub(crate) use gpu_vordie_spot_lights::GpuVordieSpotLights;
+
+#[derive(Resource, Default)]
+struct ExtractedVordieSpotLights {
+    lights: Vec<GpuVordieSpotLight>,
+}
+
+/// World space spot lights, bound to the GI raycast pass.
+#[derive(Resource, Default)]
+pub(crate) struct VordieSpotLightsBuffer {
+    pub(crate) buffer: StorageBuffer<GpuVordieSpotLights>,
+}
+
+pub(crate) struct VordieLightsPlugin {
+    pub(crate) point_lights: bool,
+    pub(crate) spot_lights: bool,
+}
+
+impl Plugin for VordieLightsPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        // The spot lights buffer is always bound in the GI raycast pass, so it is prepared even
+        // when no spot lights are extracted.
+        render_app
+            .init_resource::<ExtractedVordieSpotLights>()
+            .init_resource::<VordieSpotLightsBuffer>()
+            .add_systems(
+                Render,
+                prepare_vordie_spot_lights.in_set(RenderSet::PrepareResources),
+            );
+
+        if self.point_lights {
+            render_app.add_systems(
+                ExtractSchedule,
+                extract_vordie_point_lights.after(clear_vordie_scene),
+            );
+        }
+        if self.spot_lights {
+            render_app.add_systems(ExtractSchedule, extract_vordie_spot_lights);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_vordie_point_lights(
+    mut extracted_scene: ResMut<ExtractedVordieScene>,
+    point_lights: Extract<
+        Query<(
+            Entity,
+            &VordiePointLight,
+            &GlobalTransform,
+            Option<&InheritedVisibility>,
+        )>,
+    >,
+) {
+    for (entity, point_light, transform, inherited_visibility) in &point_lights {
+        if inherited_visibility.is_some_and(|visibility| !visibility.get()) {
+            continue;
+        }
+
+        extracted_scene.insert_point_light(
+            entity,
+            *transform,
+            point_light.radius,
+            LinearRgba::from(point_light.color) * point_light.intensity,
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn extract_vordie_spot_lights(
+    mut extracted_spot_lights: ResMut<ExtractedVordieSpotLights>,
+    spot_lights: Extract<
+        Query<(
+            &VordieSpotLight,
+            &GlobalTransform,
+            Option<&InheritedVisibility>,
+        )>,
+    >,
+) {
+    extracted_spot_lights.lights.clear();
+
+    for (spot_light, transform, inherited_visibility) in &spot_lights {
+        if inherited_visibility.is_some_and(|visibility| !visibility.get()) {
+            continue;
+        }
+
+        let direction = transform
+            .affine()
+            .transform_vector3(spot_light.direction.extend(0.0))
+            .truncate()
+            .normalize_or_zero();
+        let outer_angle = spot_light.outer_angle.max(0.0);
+        let inner_angle = spot_light.inner_angle.clamp(0.0, outer_angle);
+
+        extracted_spot_lights.lights.push(GpuVordieSpotLight {
+            color: (LinearRgba::from(spot_light.color) * spot_light.intensity).to_vec4(),
+            position: transform.translation().truncate(),
+            direction,
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+            range: spot_light.range.unwrap_or(-1.0),
+            dropoff: spot_light.dropoff.unwrap_or(-1.0),
+        });
+    }
+}
+
+fn prepare_vordie_spot_lights(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    extracted_spot_lights: Res<ExtractedVordieSpotLights>,
+    mut spot_lights_buffer: ResMut<VordieSpotLightsBuffer>,
+) {
+    let mut data = extracted_spot_lights.lights.clone();
+    let count = data.len() as u32;
+    // A storage buffer binding can't be empty.
+    if data.is_empty() {
+        data.push(GpuVordieSpotLight::default());
+    }
+
+    spot_lights_buffer
+        .buffer
+        .set(GpuVordieSpotLights { count, data });
+    spot_lights_buffer
+        .buffer
+        .write_buffer(&render_device, &render_queue);
+}
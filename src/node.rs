@@ -0,0 +1,1107 @@
+use bevy::{
+    core::FrameCount,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ComponentUniforms, DynamicUniformIndex},
+        render_asset::RenderAssets,
+        render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
+        render_phase::ViewSortedRenderPhases,
+        render_resource::*,
+        renderer::{RenderContext, RenderQueue},
+        texture::{CachedTexture, FallbackImage, GpuImage},
+        view::{ViewTarget, ViewUniformOffset, ViewUniforms},
+    },
+};
+
+use crate::{
+    lights::VordieSpotLightsBuffer, scene::VordieEmittersBuffer, GlobalIHistoryTextures, Params,
+    VordieCompositePipelineId, VordieDebugView, VordieGiMode, VordieGiPassParams, VordieJfaBackend,
+    VordieLightPipeline, VordieLightSettings, VordieNoiseParams, VordiePreviousView,
+    VordieRaySampling, VordieResetHistory, VordieScene2d, VordieSky, VordieSkyParams,
+    VordieViewTextures,
+};
+
+/// Render graph labels of the light pass stages, in the order they run.
+///
+/// Nodes can be added between two stages to read or edit the textures in
+/// [`VordieViewTextures`].
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub enum VordieNode2d {
+    /// Draws the emitters and occluders.
+    Scene,
+    /// Seeds the jump flood from the emitters and occluders.
+    Seed,
+    /// Jump flood, finds the nearest surface of each pixel.
+    JumpFlood,
+    /// Turns the nearest surfaces into distances.
+    DistanceField,
+    /// Raymarches the distance field for the GI.
+    GiRaycast,
+    /// Denoises the GI and writes the history.
+    Denoise,
+    /// Upsamples the denoised GI to the view size, without blurring it across scene edges.
+    Upsample,
+    /// Lights the scene colors with the denoised GI.
+    Composite,
+}
+
+/// Workgroup size of the compute backend, matches `vordie_jfa_compute.wgsl`.
+const JFA_WORKGROUP_SIZE: u32 = 8;
+
+#[derive(Default)]
+pub(crate) struct VordieSceneNode;
+
+impl ViewNode for VordieSceneNode {
+    type ViewQuery = &'static VordieViewTextures;
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        textures: QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        // Only entities marked as emitters or occluders are drawn here, the rest of the
+        // screen is cleared to empty space.
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("emitters_occluders"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: &textures.emitters_occluders.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &textures.transmittance.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &textures.emitter_ids.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &textures.albedo.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        // The textures are cleared even without a phase, so stale surfaces aren't lit.
+        let scene_phases = world.resource::<ViewSortedRenderPhases<VordieScene2d>>();
+        let Some(scene_phase) = scene_phases.get(&graph.view_entity()) else {
+            return Ok(());
+        };
+        if !scene_phase.items.is_empty() {
+            scene_phase.render(&mut render_pass, world, graph.view_entity());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieSeedNode;
+
+impl ViewNode for VordieSeedNode {
+    type ViewQuery = (
+        &'static VordieViewTextures,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (textures, settings_index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        // Seeds of the surfaces, then of the empty pixels for the inside of the signed distance field.
+        if *world.resource::<VordieJfaBackend>() == VordieJfaBackend::Compute {
+            let seed_pipelines = [
+                vordie_pipeline.jfa_compute_seed_pipeline_id,
+                vordie_pipeline.jfa_compute_inverse_seed_pipeline_id,
+            ]
+            .map(|id| id.and_then(|id| pipeline_cache.get_compute_pipeline(id)));
+            let [Some(seed_pipeline), Some(inverse_seed_pipeline)] = seed_pipelines else {
+                return Ok(());
+            };
+            for (pipeline, destination) in [
+                (seed_pipeline, &textures.seed),
+                (inverse_seed_pipeline, &textures.inverse_seed),
+            ] {
+                run_jfa_compute_pass(
+                    render_context,
+                    world,
+                    pipeline,
+                    &textures.emitters_occluders.default_view,
+                    &textures.emitters_occluders.default_view,
+                    destination,
+                    Params::default(),
+                    settings_index.index(),
+                );
+            }
+            return Ok(());
+        }
+
+        let seed_pipelines = [
+            vordie_pipeline.seed_pipeline_id,
+            vordie_pipeline.inverse_seed_pipeline_id,
+        ]
+        .map(|id| pipeline_cache.get_render_pipeline(id));
+        let [Some(seed_pipeline), Some(inverse_seed_pipeline)] = seed_pipelines else {
+            return Ok(());
+        };
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "post_process_bind_group",
+            &vordie_pipeline.seed_bind_group_layout,
+            &BindGroupEntries::sequential((
+                // Seed from the emitters and occluders texture
+                &textures.emitters_occluders.default_view,
+                // Use the sampler created for the pipeline
+                &vordie_pipeline.sampler,
+                // Set the settings binding, including the offset
+                settings_binding.clone(),
+            )),
+        );
+        for (pipeline, destination) in [
+            (seed_pipeline, &textures.seed),
+            (inverse_seed_pipeline, &textures.inverse_seed),
+        ] {
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("vordie_light_init"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &destination.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_render_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieJumpFloodNode;
+
+impl ViewNode for VordieJumpFloodNode {
+    type ViewQuery = (
+        &'static VordieViewTextures,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (textures, settings_index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        // Only the pipeline of the active backend is fetched, the other one may not exist.
+        let (main_pipeline, compute_pipeline) = match *world.resource::<VordieJfaBackend>() {
+            VordieJfaBackend::Fragment => (
+                pipeline_cache.get_render_pipeline(vordie_pipeline.jfa_pipeline_id),
+                None,
+            ),
+            VordieJfaBackend::Compute => (
+                None,
+                vordie_pipeline
+                    .jfa_compute_pipeline_id
+                    .and_then(|id| pipeline_cache.get_compute_pipeline(id)),
+            ),
+        };
+        if main_pipeline.is_none() && compute_pipeline.is_none() {
+            return Ok(());
+        }
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        // Begining the jump flood algorithm loop
+        // Buffer for params in the jumpflood algorithm
+        let render_device = render_context.render_device().clone();
+        let render_queue = world.resource::<RenderQueue>();
+
+        let screen_size = Vec2::new(
+            textures.emitters_occluders.texture.width() as f32,
+            textures.emitters_occluders.texture.height() as f32,
+        );
+        let offsets = jfa_offsets(screen_size);
+
+        // The surfaces and the empty pixels are flooded one after the other, sharing the ping-pong
+        // texture.
+        for (seed, voronoi) in [
+            (&textures.seed, &textures.voronoi),
+            (&textures.inverse_seed, &textures.inverse_voronoi),
+        ] {
+            let mut prev_view = &seed.default_view;
+            for (i, offset) in offsets.iter().copied().enumerate() {
+                // Ping-pong between the two textures, so the last pass writes the voronoi texture
+                let destination = if (offsets.len() - 1 - i).is_multiple_of(2) {
+                    voronoi
+                } else {
+                    &textures.jfa_ping_pong
+                };
+
+                if let Some(compute_pipeline) = compute_pipeline {
+                    run_jfa_compute_pass(
+                        render_context,
+                        world,
+                        compute_pipeline,
+                        prev_view,
+                        prev_view,
+                        destination,
+                        Params {
+                            screen_pixel_size: screen_size,
+                            offset,
+                        },
+                        settings_index.index(),
+                    );
+                    prev_view = &destination.default_view;
+                    continue;
+                }
+                let Some(main_pipeline) = main_pipeline else {
+                    return Ok(());
+                };
+
+                let mut params_buffer = UniformBuffer::<Params>::from(Params {
+                    screen_pixel_size: screen_size,
+                    offset,
+                });
+                params_buffer.write_buffer(&render_device, render_queue);
+
+                let bind_group = render_device.create_bind_group(
+                    "post_process_bind_group",
+                    &vordie_pipeline.jfa_bind_group_layout,
+                    &BindGroupEntries::sequential((
+                        // Make sure to use the source view
+                        prev_view,
+                        // Use the sampler created for the pipeline
+                        &vordie_pipeline.sampler,
+                        // Set the settings binding, including the offset
+                        settings_binding.clone(),
+                        // Create new params binding
+                        params_buffer.binding().unwrap(),
+                    )),
+                );
+
+                let mut render_pass =
+                    render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                        label: Some("vordie_light_init"),
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: &destination.default_view,
+                            resolve_target: None,
+                            ops: Operations::default(),
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                render_pass.set_render_pipeline(main_pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+                render_pass.draw(0..3, 0..1);
+
+                // Set the target for the next iteration
+                prev_view = &destination.default_view;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Offsets of the jump flood passes for a texture of the given size, halving every pass.
+pub(crate) fn jfa_offsets(screen_size: Vec2) -> Vec<f32> {
+    let passes = f32::max(screen_size.x, screen_size.y).log2().ceil() as i32;
+    (0..=passes).map(|i| 2f32.powi(passes - i - 1)).collect()
+}
+
+/// Length in pixels of cascade 0's interval, `CASCADE_INTERVAL` in the GI shader.
+const CASCADE_INTERVAL: f32 = 2.0;
+
+/// Radiance cascades needed for the last one's interval to reach across a texture of the given
+/// size, as long as the last one still fits a whole probe.
+pub(crate) fn radiance_cascade_count(size: UVec2) -> u32 {
+    let diagonal = size.as_vec2().length();
+    // Cascade `n` ends CASCADE_INTERVAL * (4^(n + 1) - 1) / 3 pixels out, its probes are
+    // 2^(n + 1) pixels wide.
+    let mut count = 1;
+    while CASCADE_INTERVAL * (4f32.powi(count as i32) - 1.0) / 3.0 < diagonal
+        && 2 << count <= size.min_element()
+    {
+        count += 1;
+    }
+    count
+}
+
+/// Runs one pass of the compute backend over every pixel of `destination`. Only the distance
+/// field pass reads `inverse_source`.
+#[allow(clippy::too_many_arguments)]
+fn run_jfa_compute_pass(
+    render_context: &mut RenderContext,
+    world: &World,
+    pipeline: &ComputePipeline,
+    source: &TextureView,
+    inverse_source: &TextureView,
+    destination: &CachedTexture,
+    params: Params,
+    settings_index: u32,
+) {
+    let vordie_pipeline = world.resource::<VordieLightPipeline>();
+    let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+    let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+        return;
+    };
+
+    let mut params_buffer = UniformBuffer::<Params>::from(params);
+    params_buffer.write_buffer(
+        render_context.render_device(),
+        world.resource::<RenderQueue>(),
+    );
+
+    let bind_group = render_context.render_device().create_bind_group(
+        "jfa_compute_bind_group",
+        &vordie_pipeline.jfa_compute_bind_group_layout,
+        &BindGroupEntries::sequential((
+            source,
+            &vordie_pipeline.sampler,
+            settings_binding,
+            params_buffer.binding().unwrap(),
+            &destination.default_view,
+            inverse_source,
+        )),
+    );
+
+    let mut compute_pass =
+        render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("vordie_jfa_compute"),
+                timestamp_writes: None,
+            });
+    compute_pass.set_pipeline(pipeline);
+    compute_pass.set_bind_group(0, &bind_group, &[settings_index]);
+    compute_pass.dispatch_workgroups(
+        destination.texture.width().div_ceil(JFA_WORKGROUP_SIZE),
+        destination.texture.height().div_ceil(JFA_WORKGROUP_SIZE),
+        1,
+    );
+}
+
+#[derive(Default)]
+pub(crate) struct VordieDistanceFieldNode;
+
+impl ViewNode for VordieDistanceFieldNode {
+    type ViewQuery = (
+        &'static VordieViewTextures,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (textures, settings_index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        if *world.resource::<VordieJfaBackend>() == VordieJfaBackend::Compute {
+            let Some(dis_field_pipeline) = vordie_pipeline
+                .jfa_compute_dis_field_pipeline_id
+                .and_then(|id| pipeline_cache.get_compute_pipeline(id))
+            else {
+                return Ok(());
+            };
+            run_jfa_compute_pass(
+                render_context,
+                world,
+                dis_field_pipeline,
+                &textures.voronoi.default_view,
+                &textures.inverse_voronoi.default_view,
+                &textures.distance_field,
+                Params::default(),
+                settings_index.index(),
+            );
+            return Ok(());
+        }
+
+        let Some(dis_field_pipeline) =
+            pipeline_cache.get_render_pipeline(vordie_pipeline.dis_field_pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "dis_field_bind_group",
+            &vordie_pipeline.dis_field_bind_group_layout,
+            &BindGroupEntries::sequential((
+                // The final jump flood result
+                &textures.voronoi.default_view,
+                // Use the sampler created for the pipeline
+                &vordie_pipeline.sampler,
+                // Set the settings binding, including the offset
+                settings_binding.clone(),
+                // The jump flood result of the inverted mask
+                &textures.inverse_voronoi.default_view,
+            )),
+        );
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("vordie_light_init"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &textures.distance_field.default_view,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(dis_field_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieGiRaycastNode;
+
+impl ViewNode for VordieGiRaycastNode {
+    type ViewQuery = (
+        &'static ViewUniformOffset,
+        &'static VordieViewTextures,
+        &'static GlobalIHistoryTextures,
+        Has<VordieResetHistory>,
+        Option<&'static VordieRaySampling>,
+        Option<&'static VordieGiMode>,
+        Option<&'static VordieSky>,
+        &'static VordieLightSettings,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (
+            view_uniform_offset,
+            textures,
+            gi_history_textures,
+            reset_history,
+            ray_sampling,
+            gi_mode,
+            sky,
+            settings,
+            settings_index,
+        ): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        if reset_history {
+            clear_gi_history(render_context, gi_history_textures);
+        }
+
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (Some(gi_raycast_pipeline), Some(radiance_cascades_pipeline)) = (
+            pipeline_cache.get_render_pipeline(vordie_pipeline.gi_raycast_pipeline_id),
+            pipeline_cache.get_render_pipeline(vordie_pipeline.radiance_cascades_pipeline_id),
+        ) else {
+            return Ok(());
+        };
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+        let Some(view_binding) = world.resource::<ViewUniforms>().uniforms.binding() else {
+            return Ok(());
+        };
+        let Some(spot_lights_binding) = world.resource::<VordieSpotLightsBuffer>().buffer.binding()
+        else {
+            return Ok(());
+        };
+        let Some(emitters_binding) = world.resource::<VordieEmittersBuffer>().buffer.binding()
+        else {
+            return Ok(());
+        };
+
+        let fallback_image = world.resource::<FallbackImage>();
+        let blue_noise = world
+            .resource::<RenderAssets<GpuImage>>()
+            .get(&vordie_pipeline.blue_noise)
+            .map_or(&fallback_image.d2.texture_view, |image| &image.texture_view);
+
+        // Until its texture is loaded a textured sky brings no light.
+        let sky_image = match sky {
+            Some(VordieSky::Texture(image)) => {
+                world.resource::<RenderAssets<GpuImage>>().get(image)
+            }
+            _ => None,
+        };
+        let sky_params = match (sky, sky_image) {
+            (Some(VordieSky::Texture(_)), None) => VordieSkyParams::default(),
+            _ => VordieSkyParams::from(sky),
+        };
+        let sky_texture =
+            sky_image.map_or(&fallback_image.d2.texture_view, |image| &image.texture_view);
+        let mut sky_buffer = UniformBuffer::<VordieSkyParams>::from(sky_params);
+        sky_buffer.write_buffer(
+            render_context.render_device(),
+            world.resource::<RenderQueue>(),
+        );
+
+        let mut noise_buffer = UniformBuffer::<VordieNoiseParams>::from(VordieNoiseParams {
+            frame: world.resource::<FrameCount>().0,
+            sampling: ray_sampling.copied().unwrap_or_default() as u32,
+        });
+        noise_buffer.write_buffer(
+            render_context.render_device(),
+            world.resource::<RenderQueue>(),
+        );
+
+        let render_device = render_context.render_device().clone();
+        let render_queue = world.resource::<RenderQueue>();
+        // The passes only differ in the bounce and cascade they read and draw.
+        let create_bind_group = |surface_radiance_view: &TextureView,
+                                 cascade_view: &TextureView,
+                                 params: VordieGiPassParams| {
+            let mut gi_pass_buffer = UniformBuffer::<VordieGiPassParams>::from(params);
+            gi_pass_buffer.write_buffer(&render_device, render_queue);
+            render_device.create_bind_group(
+                "gi_raycast_bind_group",
+                &vordie_pipeline.gi_raycast_bind_group_layout,
+                &BindGroupEntries::sequential((
+                    // Set the distance field
+                    &textures.distance_field.default_view,
+                    // Use the sampler created for the pipeline
+                    &vordie_pipeline.sampler,
+                    // Set the settings binding, including the offset
+                    settings_binding.clone(),
+                    // Set the emitters and occluders texture
+                    &textures.emitters_occluders.default_view,
+                    // Light of the last bounce
+                    surface_radiance_view,
+                    // Set the frame number and sampling strategy
+                    noise_buffer.binding().unwrap(),
+                    // View and spot lights
+                    view_binding.clone(),
+                    spot_lights_binding.clone(),
+                    // Set the occluder transmittance texture
+                    &textures.transmittance.default_view,
+                    // Emitter IDs and their falloff
+                    &textures.emitter_ids.default_view,
+                    emitters_binding.clone(),
+                    // Occluder albedo
+                    &textures.albedo.default_view,
+                    // Until it's loaded the blue noise is a single texel
+                    blue_noise,
+                    // Radiance cascade to merge or gather
+                    cascade_view,
+                    gi_pass_buffer.binding().unwrap(),
+                    // Sky of the rays that leave the view
+                    sky_texture,
+                    sky_buffer.binding().unwrap(),
+                )),
+            )
+        };
+
+        let gi_mode = gi_mode.copied().unwrap_or_default();
+        let cascade_count = match gi_mode {
+            VordieGiMode::RadianceCascades => radiance_cascade_count(UVec2::new(
+                textures.gi.texture.width(),
+                textures.gi.texture.height(),
+            )),
+            VordieGiMode::Raymarch => 0,
+        };
+        // Ping-pong between the two textures from the top cascade down, so cascade 0 ends up in
+        // `radiance_cascades`.
+        let cascade_texture = |index: u32| {
+            if index.is_multiple_of(2) {
+                &textures.radiance_cascades
+            } else {
+                &textures.radiance_cascades_ping_pong
+            }
+        };
+        // Every bounce reads the light around the surfaces the one before it left. Bounce 0 reads
+        // nothing, only the last one's GI is kept.
+        let surface_radiance_texture = |bounce: u32| {
+            if bounce.is_multiple_of(2) {
+                &textures.surface_radiance
+            } else {
+                &textures.surface_radiance_ping_pong
+            }
+        };
+        // In binding order, the settings come before the view.
+        let dynamic_offsets = [settings_index.index(), view_uniform_offset.offset];
+        for bounce in 0..=settings.u_max_bounces {
+            let surface_radiance_view = &surface_radiance_texture(bounce + 1).default_view;
+
+            for cascade in (0..cascade_count).rev() {
+                let bind_group = create_bind_group(
+                    surface_radiance_view,
+                    &cascade_texture(cascade + 1).default_view,
+                    VordieGiPassParams {
+                        cascade,
+                        cascade_count,
+                        bounce,
+                        mode: gi_mode as u32,
+                    },
+                );
+                let mut render_pass =
+                    render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                        label: Some("vordie_light_radiance_cascade"),
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: &cascade_texture(cascade).default_view,
+                            resolve_target: None,
+                            ops: Operations::default(),
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                render_pass.set_render_pipeline(radiance_cascades_pipeline);
+                render_pass.set_bind_group(0, &bind_group, &dynamic_offsets);
+                render_pass.draw(0..3, 0..1);
+            }
+
+            let bind_group = create_bind_group(
+                surface_radiance_view,
+                &textures.radiance_cascades.default_view,
+                VordieGiPassParams {
+                    cascade: 0,
+                    cascade_count,
+                    bounce,
+                    mode: gi_mode as u32,
+                },
+            );
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("vordie_light_init"),
+                color_attachments: &[
+                    Some(RenderPassColorAttachment {
+                        view: &textures.gi.default_view,
+                        resolve_target: None,
+                        ops: Operations::default(),
+                    }),
+                    Some(RenderPassColorAttachment {
+                        view: &surface_radiance_texture(bounce).default_view,
+                        resolve_target: None,
+                        ops: Operations::default(),
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_render_pipeline(gi_raycast_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &dynamic_offsets);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Clears the history textures, so the GI pass starts over without any bounce light and the
+/// denoiser without any accumulated frames.
+fn clear_gi_history(
+    render_context: &mut RenderContext,
+    gi_history_textures: &GlobalIHistoryTextures,
+) {
+    for texture in [
+        &gi_history_textures.read,
+        &gi_history_textures.write,
+        &gi_history_textures.moments_read,
+        &gi_history_textures.moments_write,
+    ] {
+        render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("vordie_clear_gi_history"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &texture.default_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Default::default()),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieDenoiseNode;
+
+impl ViewNode for VordieDenoiseNode {
+    type ViewQuery = (
+        &'static ViewUniformOffset,
+        &'static VordieViewTextures,
+        &'static GlobalIHistoryTextures,
+        &'static VordiePreviousView,
+        &'static VordieLightSettings,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (
+            view_uniform_offset,
+            textures,
+            gi_history_textures,
+            previous_view,
+            settings,
+            settings_index,
+        ): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (Some(denoise_pipeline), Some(atrous_pipeline)) = (
+            pipeline_cache.get_render_pipeline(vordie_pipeline.denoise_pipeline_id),
+            pipeline_cache.get_render_pipeline(vordie_pipeline.atrous_pipeline_id),
+        ) else {
+            return Ok(());
+        };
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+        let Some(view_binding) = world.resource::<ViewUniforms>().uniforms.binding() else {
+            return Ok(());
+        };
+
+        let screen_pixel_size = Vec2::new(
+            textures.emitters_occluders.texture.width() as f32,
+            textures.emitters_occluders.texture.height() as f32,
+        );
+        let mut params_buffer = UniformBuffer::<Params>::from(Params {
+            screen_pixel_size,
+            offset: 0.0,
+        });
+        params_buffer.write_buffer(
+            render_context.render_device(),
+            world.resource::<RenderQueue>(),
+        );
+        let mut previous_view_buffer = UniformBuffer::<VordiePreviousView>::from(*previous_view);
+        previous_view_buffer.write_buffer(
+            render_context.render_device(),
+            world.resource::<RenderQueue>(),
+        );
+
+        // Ping-pong between the light and the ping-pong texture, so the last pass writes the light
+        let iterations = settings.u_denoise_iterations;
+        let target = |pass: u32| {
+            if (iterations - pass).is_multiple_of(2) {
+                &textures.light
+            } else {
+                &textures.denoise_ping_pong
+            }
+        };
+
+        // Temporal accumulation, also estimates the variance for the à-trous passes.
+        let bind_group = render_context.render_device().create_bind_group(
+            "denoise_bind_group",
+            &vordie_pipeline.denoise_bind_group_layout,
+            &BindGroupEntries::sequential((
+                // The raw GI
+                &textures.gi.default_view,
+                // Use the sampler created for the pipeline
+                &vordie_pipeline.sampler,
+                // Past frames
+                &gi_history_textures.read.default_view,
+                // Set the settings binding, including the offset
+                settings_binding.clone(),
+                // Set the params binding
+                params_buffer.binding().unwrap(),
+                // Past moments
+                &gi_history_textures.moments_read.default_view,
+                // This and last frame's view, for reprojecting the history
+                view_binding.clone(),
+                previous_view_buffer.binding().unwrap(),
+            )),
+        );
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("vordie_light_denoise"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: &target(0).default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &gi_history_textures.write.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &gi_history_textures.moments_write.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(denoise_pipeline);
+        // In binding order, the settings come before the view.
+        render_pass.set_bind_group(
+            0,
+            &bind_group,
+            &[settings_index.index(), view_uniform_offset.offset],
+        );
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+
+        // À-trous passes, the taps spread twice as far each time.
+        for iteration in 0..iterations {
+            let source = target(iteration);
+            let destination = target(iteration + 1);
+
+            let mut params_buffer = UniformBuffer::<Params>::from(Params {
+                screen_pixel_size,
+                offset: 2.0f32.powi(iteration as i32),
+            });
+            params_buffer.write_buffer(
+                render_context.render_device(),
+                world.resource::<RenderQueue>(),
+            );
+
+            let bind_group = render_context.render_device().create_bind_group(
+                "atrous_bind_group",
+                &vordie_pipeline.atrous_bind_group_layout,
+                &BindGroupEntries::sequential((
+                    &source.default_view,
+                    // This frame's moments
+                    &gi_history_textures.moments_write.default_view,
+                    // Edges of the surfaces
+                    &textures.emitters_occluders.default_view,
+                    &textures.distance_field.default_view,
+                    settings_binding.clone(),
+                    params_buffer.binding().unwrap(),
+                )),
+            );
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("vordie_light_atrous"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &destination.default_view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_render_pipeline(atrous_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieUpsampleNode;
+
+impl ViewNode for VordieUpsampleNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static VordieViewTextures,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, textures, settings_index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(upsample_pipeline) =
+            pipeline_cache.get_render_pipeline(vordie_pipeline.upsample_pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "upsample_bind_group",
+            &vordie_pipeline.upsample_bind_group_layout,
+            &BindGroupEntries::sequential((
+                // The denoised light
+                &textures.light.default_view,
+                // The scene colors, only read here
+                view_target.main_texture_view(),
+                // Set the settings binding
+                settings_binding.clone(),
+            )),
+        );
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("vordie_light_upsample"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &textures.upsampled_light.default_view,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(upsample_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VordieCompositeNode;
+
+impl ViewNode for VordieCompositeNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static VordieViewTextures,
+        &'static GlobalIHistoryTextures,
+        &'static VordieCompositePipelineId,
+        Option<&'static VordieDebugView>,
+        &'static DynamicUniformIndex<VordieLightSettings>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (
+            view_target,
+            textures,
+            gi_history_textures,
+            composite_pipeline_id,
+            debug_view,
+            settings_index,
+        ): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let vordie_pipeline = world.resource::<VordieLightPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(composite_pipeline) = pipeline_cache.get_render_pipeline(composite_pipeline_id.0)
+        else {
+            return Ok(());
+        };
+
+        let settings_uniforms = world.resource::<ComponentUniforms<VordieLightSettings>>();
+        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        // The source is still the scene as it was drawn before the light pass.
+        let view_texture = view_target.post_process_write();
+
+        let debug_texture = match debug_view.copied().unwrap_or_default() {
+            VordieDebugView::Scene => Some(&textures.emitters_occluders),
+            VordieDebugView::Seeds => Some(&textures.seed),
+            VordieDebugView::Voronoi => Some(&textures.voronoi),
+            VordieDebugView::DistanceField => Some(&textures.distance_field),
+            VordieDebugView::Gi => Some(&textures.gi),
+            VordieDebugView::History => Some(&gi_history_textures.read),
+            VordieDebugView::Final => None,
+        };
+        // The debug view doesn't read the settings.
+        let settings_offset = [settings_index.index()];
+        let (bind_group, dynamic_offsets): (_, &[u32]) = match debug_texture {
+            Some(debug_texture) => (
+                render_context.render_device().create_bind_group(
+                    "debug_bind_group",
+                    &vordie_pipeline.debug_bind_group_layout,
+                    &BindGroupEntries::sequential((
+                        // The buffer to show
+                        &debug_texture.default_view,
+                        // Use the sampler created for the pipeline
+                        &vordie_pipeline.sampler,
+                    )),
+                ),
+                &[],
+            ),
+            None => (
+                render_context.render_device().create_bind_group(
+                    "composite_bind_group",
+                    &vordie_pipeline.composite_bind_group_layout,
+                    &BindGroupEntries::sequential((
+                        // The scene colors
+                        view_texture.source,
+                        // Use the sampler created for the pipeline
+                        &vordie_pipeline.sampler,
+                        // The denoised light at the view size
+                        &textures.upsampled_light.default_view,
+                        // Emitters are shown as they are, not lit
+                        &textures.emitters_occluders.default_view,
+                        // Set the settings binding, including the offset
+                        settings_binding.clone(),
+                    )),
+                ),
+                &settings_offset,
+            ),
+        };
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("vordie_light_composite"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: view_texture.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(composite_pipeline);
+        render_pass.set_bind_group(0, &bind_group, dynamic_offsets);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
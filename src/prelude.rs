@@ -1,2 +1,17 @@
+pub use crate::VordieDebugView;
+pub use crate::VordieEmitter;
+pub use crate::VordieGiMode;
+pub use crate::VordieJfaBackend;
+pub use crate::VordieJfaPrecision;
 pub use crate::VordieLight2DPlugin;
+pub use crate::VordieLightPassOrder;
 pub use crate::VordieLightSettings;
+pub use crate::VordieOccluder;
+pub use crate::VordiePointLight;
+pub use crate::VordiePointLightBundle;
+pub use crate::VordieRaySampling;
+pub use crate::VordieResetHistory;
+pub use crate::VordieResolutionScale;
+pub use crate::VordieSky;
+pub use crate::VordieSpotLight;
+pub use crate::VordieSpotLightBundle;
@@ -0,0 +1,1008 @@
+use std::ops::Range;
+
+use bevy::{
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        system::{
+            lifetimeless::{Read, SRes},
+            SystemParamItem,
+        },
+    },
+    math::{Affine3A, FloatOrd},
+    prelude::*,
+    render::{
+        mesh::{GpuBufferInfo, GpuMesh, MeshVertexBufferLayoutRef},
+        render_asset::RenderAssets,
+        render_phase::{
+            sort_phase_system, AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctionId,
+            DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand, RenderCommandResult,
+            SetItemPipeline, SortedPhaseItem, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{FallbackImage, GpuImage},
+        view::{ExtractedView, ViewUniform, ViewUniformOffset, ViewUniforms},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    sprite::Mesh2dHandle,
+    utils::HashMap,
+};
+
+use crate::VordieLightSettings;
+
+/// Marks a 2D mesh or sprite as a light emitter.
+///
+/// The entity is drawn into the emitter/occluder texture with its own color,
+/// so it lights the scene in that color.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VordieEmitter {
+    /// Multiplier on the emitted light.
+    pub intensity: f32,
+    /// How far the light reaches, `None` uses the camera's `u_emission_range`.
+    pub range: Option<f32>,
+    /// How fast the light fades over its range, `None` uses the camera's `u_emission_dropoff`.
+    pub dropoff: Option<f32>,
+}
+impl Default for VordieEmitter {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            range: None,
+            dropoff: None,
+        }
+    }
+}
+
+/// Marks a 2D mesh or sprite as a light occluder.
+///
+/// The entity is drawn into the emitter/occluder texture as a black surface,
+/// so it blocks light and casts shadows.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VordieOccluder {
+    /// Fraction of light, per color channel, that passes through one world unit of the occluder.
+    ///
+    /// Rays are tinted and attenuated Beer–Lambert style while inside it, so a thick
+    /// occluder lets through less light than a thin one.
+    /// Black (the default) is fully opaque.
+    pub transmittance: Color,
+}
+impl Default for VordieOccluder {
+    fn default() -> Self {
+        Self {
+            transmittance: Color::BLACK,
+        }
+    }
+}
+
+pub(crate) struct VordieScenePlugin {
+    pub(crate) emitters_and_occluders: bool,
+}
+
+impl Plugin for VordieScenePlugin {
+    fn build(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<DrawFunctions<VordieScene2d>>()
+            .init_resource::<ViewSortedRenderPhases<VordieScene2d>>()
+            .init_resource::<ExtractedVordieScene>()
+            .init_resource::<VordieSceneMeta>()
+            .init_resource::<VordieSceneImageBindGroups>()
+            .init_resource::<VordieEmittersBuffer>()
+            .add_render_command::<VordieScene2d, DrawVordieScene>()
+            .add_systems(
+                ExtractSchedule,
+                (extract_vordie_scene_phases, clear_vordie_scene),
+            )
+            .add_systems(
+                Render,
+                (
+                    queue_vordie_scene.in_set(RenderSet::Queue),
+                    sort_phase_system::<VordieScene2d>.in_set(RenderSet::PhaseSort),
+                    prepare_vordie_scene_instances.in_set(RenderSet::PrepareResources),
+                    prepare_vordie_scene_bind_groups.in_set(RenderSet::PrepareBindGroups),
+                ),
+            );
+
+        if self.emitters_and_occluders {
+            render_app.add_systems(
+                ExtractSchedule,
+                extract_vordie_scene.after(clear_vordie_scene),
+            );
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<VordieScenePipeline>()
+            .init_resource::<SpecializedMeshPipelines<VordieScenePipeline>>();
+    }
+}
+
+/// Phase item for everything drawn into the emitter/occluder texture.
+pub struct VordieScene2d {
+    pub sort_key: FloatOrd,
+    pub entity: Entity,
+    /// Draws the entity's point light rather than its emitter or occluder, it can have both.
+    pub point_light: bool,
+    pub pipeline: CachedRenderPipelineId,
+    pub draw_function: DrawFunctionId,
+    pub batch_range: Range<u32>,
+    pub extra_index: PhaseItemExtraIndex,
+}
+
+impl PhaseItem for VordieScene2d {
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    #[inline]
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+
+    #[inline]
+    fn batch_range(&self) -> &Range<u32> {
+        &self.batch_range
+    }
+
+    #[inline]
+    fn batch_range_mut(&mut self) -> &mut Range<u32> {
+        &mut self.batch_range
+    }
+
+    #[inline]
+    fn extra_index(&self) -> PhaseItemExtraIndex {
+        self.extra_index
+    }
+
+    #[inline]
+    fn batch_range_and_extra_index_mut(&mut self) -> (&mut Range<u32>, &mut PhaseItemExtraIndex) {
+        (&mut self.batch_range, &mut self.extra_index)
+    }
+}
+
+impl SortedPhaseItem for VordieScene2d {
+    type SortKey = FloatOrd;
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+}
+
+impl CachedRenderPipelinePhaseItem for VordieScene2d {
+    #[inline]
+    fn cached_pipeline(&self) -> CachedRenderPipelineId {
+        self.pipeline
+    }
+}
+
+enum VordieSceneShape {
+    Mesh(AssetId<Mesh>),
+    Disc {
+        radius: f32,
+    },
+    Sprite {
+        rect: Option<Rect>,
+        custom_size: Option<Vec2>,
+        anchor: Vec2,
+        flip_x: bool,
+        flip_y: bool,
+    },
+}
+
+pub(crate) struct ExtractedVordieSceneItem {
+    shape: VordieSceneShape,
+    transform: GlobalTransform,
+    color: LinearRgba,
+    image: Option<AssetId<Image>>,
+    emitter: bool,
+    /// Index into the emitters storage buffer, 0 when the camera's falloff is used.
+    emitter_id: u32,
+    transmittance: LinearRgba,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedVordieScene {
+    pub(crate) items: EntityHashMap<ExtractedVordieSceneItem>,
+    /// Apart from `items` so that a point light doesn't replace an emitter on the same entity.
+    pub(crate) point_lights: EntityHashMap<ExtractedVordieSceneItem>,
+    emitters: Vec<GpuVordieEmitter>,
+}
+
+impl ExtractedVordieScene {
+    /// Adds a point light's emissive disc, which only exists in the emitter texture.
+    pub(crate) fn insert_point_light(
+        &mut self,
+        entity: Entity,
+        transform: GlobalTransform,
+        radius: f32,
+        color: LinearRgba,
+    ) {
+        self.point_lights.insert(
+            entity,
+            ExtractedVordieSceneItem {
+                shape: VordieSceneShape::Disc { radius },
+                transform,
+                color,
+                image: None,
+                emitter: true,
+                emitter_id: 0,
+                transmittance: LinearRgba::BLACK,
+            },
+        );
+    }
+
+    /// Every item along with its entity and whether it's a point light.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = ((Entity, bool), &ExtractedVordieSceneItem)> {
+        let items = self
+            .items
+            .iter()
+            .map(|(entity, item)| ((*entity, false), item));
+        let point_lights = self
+            .point_lights
+            .iter()
+            .map(|(entity, item)| ((*entity, true), item));
+        items.chain(point_lights)
+    }
+
+    fn get(&self, item: &VordieScene2d) -> Option<&ExtractedVordieSceneItem> {
+        match item.point_light {
+            true => self.point_lights.get(&item.entity),
+            false => self.items.get(&item.entity),
+        }
+    }
+
+    fn push_emitter(&mut self, emitter: &VordieEmitter) -> u32 {
+        self.emitters.push(GpuVordieEmitter {
+            intensity: emitter.intensity,
+            range: emitter.range.unwrap_or(-1.0),
+            dropoff: emitter.dropoff.unwrap_or(-1.0),
+        });
+        self.emitters.len() as u32 - 1
+    }
+}
+
+// In its own module to scope the dead code allow, see `VordieLightSettings`.
+#[allow(dead_code)]
+mod gpu_vordie_emitter {
+    use super::*;
+
+    /// Falloff of a single emitter, negative range and dropoff fall back to the camera's settings.
+    #[derive(Clone, Copy, ShaderType)]
+    pub(crate) struct GpuVordieEmitter {
+        pub(super) intensity: f32,
+        pub(super) range: f32,
+        pub(super) dropoff: f32,
+    }
+}
+pub(crate) use gpu_vordie_emitter::GpuVordieEmitter;
+impl Default for GpuVordieEmitter {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            range: -1.0,
+            dropoff: -1.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+mod gpu_vordie_emitters {
+    use super::*;
+
+    #[derive(Default, ShaderType)]
+    pub(crate) struct GpuVordieEmitters {
+        #[size(runtime)]
+        pub(super) data: Vec<GpuVordieEmitter>,
+    }
+}
+pub(crate) use gpu_vordie_emitters::GpuVordieEmitters;
+
+/// Per emitter falloff, indexed by the emitter ID texture in the GI raycast pass.
+#[derive(Resource, Default)]
+pub(crate) struct VordieEmittersBuffer {
+    pub(crate) buffer: StorageBuffer<GpuVordieEmitters>,
+}
+
+fn extract_vordie_scene_phases(
+    mut commands: Commands,
+    mut scene_phases: ResMut<ViewSortedRenderPhases<VordieScene2d>>,
+    cameras: Extract<Query<(Entity, &Camera), With<VordieLightSettings>>>,
+    mut live_entities: Local<EntityHashSet>,
+) {
+    live_entities.clear();
+
+    for (entity, camera) in &cameras {
+        if !camera.is_active {
+            continue;
+        }
+
+        commands.get_or_spawn(entity);
+        scene_phases.insert_or_clear(entity);
+
+        live_entities.insert(entity);
+    }
+
+    // Clear out all dead views.
+    scene_phases.retain(|camera_entity, _| live_entities.contains(camera_entity));
+}
+
+/// Starts the frame's scene empty, the extract systems then fill it in.
+pub(crate) fn clear_vordie_scene(mut extracted_scene: ResMut<ExtractedVordieScene>) {
+    extracted_scene.items.clear();
+    extracted_scene.point_lights.clear();
+    // Emitter 0 is shared by everything that uses the camera's falloff.
+    extracted_scene.emitters.clear();
+    extracted_scene.emitters.push(GpuVordieEmitter::default());
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_vordie_scene(
+    mut extracted_scene: ResMut<ExtractedVordieScene>,
+    color_materials: Extract<Res<Assets<ColorMaterial>>>,
+    texture_atlases: Extract<Res<Assets<TextureAtlasLayout>>>,
+    mesh_query: Extract<
+        Query<
+            (
+                Entity,
+                &ViewVisibility,
+                &Mesh2dHandle,
+                &GlobalTransform,
+                Option<&Handle<ColorMaterial>>,
+                Option<&VordieEmitter>,
+                Option<&VordieOccluder>,
+            ),
+            Or<(With<VordieEmitter>, With<VordieOccluder>)>,
+        >,
+    >,
+    sprite_query: Extract<
+        Query<
+            (
+                Entity,
+                &ViewVisibility,
+                &Sprite,
+                &GlobalTransform,
+                &Handle<Image>,
+                Option<&TextureAtlas>,
+                Option<&VordieEmitter>,
+                Option<&VordieOccluder>,
+            ),
+            Or<(With<VordieEmitter>, With<VordieOccluder>)>,
+        >,
+    >,
+) {
+    for (entity, view_visibility, mesh, transform, material, emitter, occluder) in &mesh_query {
+        if !view_visibility.get() {
+            continue;
+        }
+
+        let material = material.and_then(|handle| color_materials.get(handle));
+        let emitter_id = emitter.map_or(0, |emitter| extracted_scene.push_emitter(emitter));
+        extracted_scene.items.insert(
+            entity,
+            ExtractedVordieSceneItem {
+                shape: VordieSceneShape::Mesh(mesh.0.id()),
+                transform: *transform,
+                color: material.map_or(LinearRgba::WHITE, |material| material.color.into()),
+                image: material
+                    .and_then(|material| material.texture.as_ref())
+                    .map(Handle::id),
+                emitter: emitter.is_some(),
+                emitter_id,
+                transmittance: occluder_transmittance(emitter.is_some(), occluder),
+            },
+        );
+    }
+
+    for (entity, view_visibility, sprite, transform, handle, sheet, emitter, occluder) in
+        &sprite_query
+    {
+        if !view_visibility.get() {
+            continue;
+        }
+
+        let atlas_rect = sheet.and_then(|s| s.texture_rect(&texture_atlases));
+        let rect = match (atlas_rect, sprite.rect) {
+            (None, None) => None,
+            (None, Some(sprite_rect)) => Some(sprite_rect),
+            (Some(atlas_rect), None) => Some(atlas_rect.as_rect()),
+            (Some(atlas_rect), Some(mut sprite_rect)) => {
+                sprite_rect.min += atlas_rect.min.as_vec2();
+                sprite_rect.max += atlas_rect.min.as_vec2();
+
+                Some(sprite_rect)
+            }
+        };
+
+        let emitter_id = emitter.map_or(0, |emitter| extracted_scene.push_emitter(emitter));
+        extracted_scene.items.insert(
+            entity,
+            ExtractedVordieSceneItem {
+                shape: VordieSceneShape::Sprite {
+                    rect,
+                    custom_size: sprite.custom_size,
+                    anchor: sprite.anchor.as_vec(),
+                    flip_x: sprite.flip_x,
+                    flip_y: sprite.flip_y,
+                },
+                transform: *transform,
+                color: sprite.color.into(),
+                image: Some(handle.id()),
+                emitter: emitter.is_some(),
+                emitter_id,
+                transmittance: occluder_transmittance(emitter.is_some(), occluder),
+            },
+        );
+    }
+}
+
+/// Emitters are always opaque, an entity marked as both is treated as an emitter.
+fn occluder_transmittance(emitter: bool, occluder: Option<&VordieOccluder>) -> LinearRgba {
+    match occluder {
+        Some(occluder) if !emitter => occluder.transmittance.into(),
+        _ => LinearRgba::BLACK,
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct VordieScenePipeline {
+    view_layout: BindGroupLayout,
+    instance_layout: BindGroupLayout,
+    texture_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+    quad_pipeline_id: CachedRenderPipelineId,
+}
+
+impl VordieScenePipeline {
+    fn descriptor(
+        &self,
+        shader_defs: Vec<ShaderDefVal>,
+        buffers: Vec<VertexBufferLayout>,
+        topology: PrimitiveTopology,
+    ) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("vordie_scene_pipeline".into()),
+            layout: vec![
+                self.view_layout.clone(),
+                self.instance_layout.clone(),
+                self.texture_layout.clone(),
+            ],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                shader_defs: shader_defs.clone(),
+                entry_point: "vertex".into(),
+                buffers,
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![
+                    // Emitters and occluders
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    // Occluder transmittance
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    // Emitter IDs
+                    Some(ColorTargetState {
+                        format: TextureFormat::R32Uint,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    // Occluder albedo
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                // Sprites can be flipped and meshes can be mirrored by their transform,
+                // both faces need to end up in the scene texture.
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            push_constant_ranges: vec![],
+        }
+    }
+}
+
+impl FromWorld for VordieScenePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let view_layout = render_device.create_bind_group_layout(
+            "vordie_scene_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                uniform_buffer::<ViewUniform>(true),
+            ),
+        );
+        let instance_layout = render_device.create_bind_group_layout(
+            "vordie_scene_instance_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                uniform_buffer::<VordieSceneInstance>(true),
+            ),
+        );
+        let texture_layout = render_device.create_bind_group_layout(
+            "vordie_scene_texture_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // The sprite or material texture, used for its color and alpha mask
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/vordie_emiters_occs.wgsl");
+
+        let mut pipeline = Self {
+            view_layout,
+            instance_layout,
+            texture_layout,
+            shader,
+            quad_pipeline_id: CachedRenderPipelineId::INVALID,
+        };
+
+        // Sprites and discs don't have a mesh, the quad is generated in the vertex shader.
+        let quad_descriptor =
+            pipeline.descriptor(vec!["QUAD".into()], vec![], PrimitiveTopology::TriangleList);
+        pipeline.quad_pipeline_id = world
+            .resource::<PipelineCache>()
+            .queue_render_pipeline(quad_descriptor);
+
+        pipeline
+    }
+}
+
+impl SpecializedMeshPipeline for VordieScenePipeline {
+    type Key = PrimitiveTopology;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayoutRef,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut shader_defs = Vec::new();
+        let mut vertex_attributes = vec![Mesh::ATTRIBUTE_POSITION.at_shader_location(0)];
+
+        if layout.0.contains(Mesh::ATTRIBUTE_UV_0) {
+            shader_defs.push("VERTEX_UVS".into());
+            vertex_attributes.push(Mesh::ATTRIBUTE_UV_0.at_shader_location(1));
+        }
+
+        let vertex_buffer_layout = layout.0.get_layout(&vertex_attributes)?;
+
+        Ok(self.descriptor(shader_defs, vec![vertex_buffer_layout], key))
+    }
+}
+
+#[allow(dead_code)]
+mod vordie_scene_instance {
+    use super::*;
+
+    #[derive(Clone, ShaderType)]
+    pub(super) struct VordieSceneInstance {
+        pub(super) world_from_local: Mat4,
+        pub(super) color: Vec4,
+        pub(super) uv_offset_scale: Vec4,
+        pub(super) transmittance: Vec4,
+        pub(super) flags: u32,
+        pub(super) emitter_id: u32,
+    }
+}
+use vordie_scene_instance::VordieSceneInstance;
+
+impl VordieSceneInstance {
+    const FLAG_EMITTER: u32 = 1 << 0;
+    const FLAG_DISC: u32 = 1 << 1;
+}
+
+#[derive(Resource, Default)]
+struct VordieSceneMeta {
+    instances: DynamicUniformBuffer<VordieSceneInstance>,
+    /// Keyed by entity and whether it's the point light, like `VordieScene2d`.
+    offsets: HashMap<(Entity, bool), u32>,
+    instance_bind_group: Option<BindGroup>,
+}
+
+#[derive(Resource, Default)]
+struct VordieSceneImageBindGroups {
+    values: HashMap<AssetId<Image>, BindGroup>,
+    fallback: Option<BindGroup>,
+}
+
+#[derive(Component)]
+struct VordieSceneViewBindGroup {
+    value: BindGroup,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_vordie_scene(
+    draw_functions: Res<DrawFunctions<VordieScene2d>>,
+    pipeline: Res<VordieScenePipeline>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<VordieScenePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted_scene: Res<ExtractedVordieScene>,
+    meshes: Res<RenderAssets<GpuMesh>>,
+    images: Res<RenderAssets<GpuImage>>,
+    mut scene_phases: ResMut<ViewSortedRenderPhases<VordieScene2d>>,
+    views: Query<Entity, (With<ExtractedView>, With<VordieLightSettings>)>,
+) {
+    let draw_function = draw_functions.read().id::<DrawVordieScene>();
+
+    for view_entity in &views {
+        let Some(scene_phase) = scene_phases.get_mut(&view_entity) else {
+            continue;
+        };
+
+        for ((entity, point_light), item) in extracted_scene.iter() {
+            let pipeline_id = match item.shape {
+                VordieSceneShape::Mesh(mesh_id) => {
+                    let Some(gpu_mesh) = meshes.get(mesh_id) else {
+                        continue;
+                    };
+                    let Ok(pipeline_id) = pipelines.specialize(
+                        &pipeline_cache,
+                        &pipeline,
+                        gpu_mesh.primitive_topology(),
+                        &gpu_mesh.layout,
+                    ) else {
+                        continue;
+                    };
+                    pipeline_id
+                }
+                VordieSceneShape::Sprite { .. } => {
+                    if item.image.and_then(|image| images.get(image)).is_none() {
+                        continue;
+                    }
+                    pipeline.quad_pipeline_id
+                }
+                VordieSceneShape::Disc { .. } => pipeline.quad_pipeline_id,
+            };
+
+            scene_phase.add(VordieScene2d {
+                sort_key: FloatOrd(item.transform.translation().z),
+                entity,
+                point_light,
+                pipeline: pipeline_id,
+                draw_function,
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::NONE,
+            });
+        }
+    }
+}
+
+fn prepare_vordie_scene_instances(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    extracted_scene: Res<ExtractedVordieScene>,
+    images: Res<RenderAssets<GpuImage>>,
+    mut scene_meta: ResMut<VordieSceneMeta>,
+    mut emitters_buffer: ResMut<VordieEmittersBuffer>,
+) {
+    let scene_meta = &mut *scene_meta;
+    scene_meta.instances.clear();
+    scene_meta.offsets.clear();
+
+    for (key, item) in extracted_scene.iter() {
+        let (world_from_local, uv_offset_scale) = match item.shape {
+            VordieSceneShape::Mesh(_) => (item.transform.compute_matrix(), Vec4::ZERO),
+            // Only the position is used, the radius is already in world units.
+            VordieSceneShape::Disc { radius } => (
+                Mat4::from_translation(item.transform.translation())
+                    * Mat4::from_scale(Vec3::new(radius * 2.0, radius * 2.0, 1.0))
+                    * Mat4::from_translation(Vec3::new(-0.5, -0.5, 0.0)),
+                Vec4::new(0.0, 0.0, 1.0, 1.0),
+            ),
+            VordieSceneShape::Sprite {
+                rect,
+                custom_size,
+                anchor,
+                flip_x,
+                flip_y,
+            } => {
+                let Some(gpu_image) = item.image.and_then(|image| images.get(image)) else {
+                    continue;
+                };
+                let image_size = gpu_image.size.as_vec2();
+
+                // Same quad and UV layout as Bevy's own sprite batching.
+                let mut quad_size = image_size;
+                let mut uv_offset_scale = if let Some(rect) = rect {
+                    let rect_size = rect.size();
+                    quad_size = rect_size;
+                    Vec4::new(
+                        rect.min.x / image_size.x,
+                        rect.max.y / image_size.y,
+                        rect_size.x / image_size.x,
+                        -rect_size.y / image_size.y,
+                    )
+                } else {
+                    Vec4::new(0.0, 1.0, 1.0, -1.0)
+                };
+
+                if flip_x {
+                    uv_offset_scale.x += uv_offset_scale.z;
+                    uv_offset_scale.z *= -1.0;
+                }
+                if flip_y {
+                    uv_offset_scale.y += uv_offset_scale.w;
+                    uv_offset_scale.w *= -1.0;
+                }
+
+                if let Some(custom_size) = custom_size {
+                    quad_size = custom_size;
+                }
+
+                let transform = item.transform.affine()
+                    * Affine3A::from_scale_rotation_translation(
+                        quad_size.extend(1.0),
+                        Quat::IDENTITY,
+                        (quad_size * (-anchor - Vec2::splat(0.5))).extend(0.0),
+                    );
+
+                (Mat4::from(transform), uv_offset_scale)
+            }
+        };
+
+        let offset = scene_meta.instances.push(&VordieSceneInstance {
+            world_from_local,
+            color: item.color.to_vec4(),
+            uv_offset_scale,
+            transmittance: item.transmittance.to_vec4(),
+            flags: match (item.emitter, &item.shape) {
+                (_, VordieSceneShape::Disc { .. }) => {
+                    VordieSceneInstance::FLAG_EMITTER | VordieSceneInstance::FLAG_DISC
+                }
+                (true, _) => VordieSceneInstance::FLAG_EMITTER,
+                (false, _) => 0,
+            },
+            emitter_id: item.emitter_id,
+        });
+        scene_meta.offsets.insert(key, offset);
+    }
+
+    scene_meta
+        .instances
+        .write_buffer(&render_device, &render_queue);
+
+    emitters_buffer.buffer.set(GpuVordieEmitters {
+        data: extracted_scene.emitters.clone(),
+    });
+    emitters_buffer
+        .buffer
+        .write_buffer(&render_device, &render_queue);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_vordie_scene_bind_groups(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<VordieScenePipeline>,
+    view_uniforms: Res<ViewUniforms>,
+    extracted_scene: Res<ExtractedVordieScene>,
+    images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
+    mut scene_meta: ResMut<VordieSceneMeta>,
+    mut image_bind_groups: ResMut<VordieSceneImageBindGroups>,
+    views: Query<Entity, (With<ExtractedView>, With<VordieLightSettings>)>,
+) {
+    let Some(view_binding) = view_uniforms.uniforms.binding() else {
+        return;
+    };
+
+    for entity in &views {
+        commands.entity(entity).insert(VordieSceneViewBindGroup {
+            value: render_device.create_bind_group(
+                "vordie_scene_view_bind_group",
+                &pipeline.view_layout,
+                &BindGroupEntries::single(view_binding.clone()),
+            ),
+        });
+    }
+
+    scene_meta.instance_bind_group = scene_meta.instances.binding().map(|binding| {
+        render_device.create_bind_group(
+            "vordie_scene_instance_bind_group",
+            &pipeline.instance_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+
+    let image_bind_groups = &mut *image_bind_groups;
+    image_bind_groups.values.clear();
+    for image_id in extracted_scene.iter().filter_map(|(_, item)| item.image) {
+        let Some(gpu_image) = images.get(image_id) else {
+            continue;
+        };
+        image_bind_groups.values.entry(image_id).or_insert_with(|| {
+            render_device.create_bind_group(
+                "vordie_scene_texture_bind_group",
+                &pipeline.texture_layout,
+                &BindGroupEntries::sequential((&gpu_image.texture_view, &gpu_image.sampler)),
+            )
+        });
+    }
+    image_bind_groups.fallback.get_or_insert_with(|| {
+        render_device.create_bind_group(
+            "vordie_scene_fallback_texture_bind_group",
+            &pipeline.texture_layout,
+            &BindGroupEntries::sequential((
+                &fallback_image.d2.texture_view,
+                &fallback_image.d2.sampler,
+            )),
+        )
+    });
+}
+
+type DrawVordieScene = (
+    SetItemPipeline,
+    SetVordieSceneViewBindGroup<0>,
+    SetVordieSceneInstanceBindGroup<1>,
+    SetVordieSceneTextureBindGroup<2>,
+    DrawVordieSceneItem,
+);
+
+struct SetVordieSceneViewBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetVordieSceneViewBindGroup<I> {
+    type Param = ();
+    type ViewQuery = (Read<ViewUniformOffset>, Read<VordieSceneViewBindGroup>);
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        (view_uniform, view_bind_group): (&'w ViewUniformOffset, &'w VordieSceneViewBindGroup),
+        _entity: Option<()>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.set_bind_group(I, &view_bind_group.value, &[view_uniform.offset]);
+
+        RenderCommandResult::Success
+    }
+}
+
+struct SetVordieSceneInstanceBindGroup<const I: usize>;
+impl<const I: usize> RenderCommand<VordieScene2d> for SetVordieSceneInstanceBindGroup<I> {
+    type Param = SRes<VordieSceneMeta>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        item: &VordieScene2d,
+        _view: (),
+        _entity: Option<()>,
+        scene_meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let scene_meta = scene_meta.into_inner();
+        let (Some(bind_group), Some(offset)) = (
+            scene_meta.instance_bind_group.as_ref(),
+            scene_meta.offsets.get(&(item.entity, item.point_light)),
+        ) else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, bind_group, &[*offset]);
+
+        RenderCommandResult::Success
+    }
+}
+
+struct SetVordieSceneTextureBindGroup<const I: usize>;
+impl<const I: usize> RenderCommand<VordieScene2d> for SetVordieSceneTextureBindGroup<I> {
+    type Param = (SRes<ExtractedVordieScene>, SRes<VordieSceneImageBindGroups>);
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        item: &VordieScene2d,
+        _view: (),
+        _entity: Option<()>,
+        (extracted_scene, image_bind_groups): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let image_bind_groups = image_bind_groups.into_inner();
+        let Some(scene_item) = extracted_scene.into_inner().get(item) else {
+            return RenderCommandResult::Failure;
+        };
+        let bind_group = match scene_item.image {
+            Some(image_id) => image_bind_groups.values.get(&image_id),
+            None => image_bind_groups.fallback.as_ref(),
+        };
+        let Some(bind_group) = bind_group else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, bind_group, &[]);
+
+        RenderCommandResult::Success
+    }
+}
+
+struct DrawVordieSceneItem;
+impl RenderCommand<VordieScene2d> for DrawVordieSceneItem {
+    type Param = (SRes<ExtractedVordieScene>, SRes<RenderAssets<GpuMesh>>);
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        item: &VordieScene2d,
+        _view: (),
+        _entity: Option<()>,
+        (extracted_scene, meshes): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(scene_item) = extracted_scene.into_inner().get(item) else {
+            return RenderCommandResult::Failure;
+        };
+
+        match scene_item.shape {
+            VordieSceneShape::Mesh(mesh_id) => {
+                let Some(gpu_mesh) = meshes.into_inner().get(mesh_id) else {
+                    return RenderCommandResult::Failure;
+                };
+
+                pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+                match &gpu_mesh.buffer_info {
+                    GpuBufferInfo::Indexed {
+                        buffer,
+                        index_format,
+                        count,
+                    } => {
+                        pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                        pass.draw_indexed(0..*count, 0, item.batch_range().clone());
+                    }
+                    GpuBufferInfo::NonIndexed => {
+                        pass.draw(0..gpu_mesh.vertex_count, item.batch_range().clone());
+                    }
+                }
+            }
+            // Two triangles, generated in the vertex shader.
+            VordieSceneShape::Sprite { .. } | VordieSceneShape::Disc { .. } => {
+                pass.draw(0..6, item.batch_range().clone());
+            }
+        }
+
+        RenderCommandResult::Success
+    }
+}
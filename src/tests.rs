@@ -0,0 +1,1160 @@
+//! Runs the seed, jump flood and distance field shaders outside of bevy on whatever adapter is
+//! available, so the fragment and compute backends can be compared with each other and with a
+//! brute force distance field. The render world systems keeping state across frames are run on
+//! a bare world.
+
+use std::borrow::Cow;
+
+use bevy::{
+    core_pipeline::core_2d::graph::Core2d,
+    ecs::system::RunSystemOnce,
+    math::{Mat4, UVec2, UVec4, Vec2, Vec3},
+    prelude::{Assets, Color, Entity, GlobalTransform, Handle, TextureAtlasLayout, World},
+    render::{
+        camera::{CameraOutputMode, ExtractedCamera},
+        extract_component::ExtractComponent,
+        render_graph::RenderSubGraph,
+        render_resource::encase,
+        view::{ExtractedView, ViewVisibility},
+        MainWorld,
+    },
+    sprite::{ColorMaterial, Mesh2dHandle},
+    utils::HashMap,
+};
+use naga_oil::compose::{
+    ComposableModuleDescriptor, Composer, NagaModuleDescriptor, ShaderDefValue, ShaderLanguage,
+};
+
+use crate::{
+    lights::extract_vordie_point_lights,
+    node::{jfa_offsets, radiance_cascade_count},
+    prepare_previous_views,
+    scene::{clear_vordie_scene, extract_vordie_scene, ExtractedVordieScene},
+    Params, VordieEmitter, VordieJfaBackend, VordieJfaPrecision, VordieLightSettings,
+    VordiePointLight, VordiePreviousView, VordiePreviousViews, VordieResetHistory,
+    VordieResolutionScale, VordieSky, VordieSkyParams,
+};
+
+// Stand-in for bevy's fullscreen vertex shader, the same triangle covering the screen.
+const FULLSCREEN_SHADER: &str = r"
+#define_import_path bevy_core_pipeline::fullscreen_vertex_shader
+
+struct FullscreenVertexOutput {
+    @builtin(position)
+    position: vec4<f32>,
+    @location(0)
+    uv: vec2<f32>,
+};
+
+@vertex
+fn fullscreen_vertex_shader(@builtin(vertex_index) vertex_index: u32) -> FullscreenVertexOutput {
+    let uv = vec2<f32>(f32(vertex_index >> 1u), f32(vertex_index & 1u)) * 2.0;
+    let clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+
+    return FullscreenVertexOutput(clip_position, uv);
+}
+";
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    composer: Composer,
+    sampler: wgpu::Sampler,
+}
+
+impl Gpu {
+    /// `None` when there is no adapter with compute support, the tests are skipped then.
+    fn new() -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = futures_lite::future::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        )?;
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return None;
+        }
+        let (device, queue) = futures_lite::future::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_limits: adapter.limits(),
+                ..Default::default()
+            },
+            None,
+        ))
+        .ok()?;
+
+        let mut composer = Composer::default();
+        composer
+            .add_composable_module(ComposableModuleDescriptor {
+                source: FULLSCREEN_SHADER,
+                file_path: "fullscreen.wgsl",
+                language: ShaderLanguage::Wgsl,
+                ..Default::default()
+            })
+            .unwrap();
+        // Imported by asset path, bevy names the module after it.
+        composer
+            .add_composable_module(ComposableModuleDescriptor {
+                source: include_str!("../assets/shaders/vordie_jfa_functions.wgsl"),
+                file_path: "shaders/vordie_jfa_functions.wgsl",
+                language: ShaderLanguage::Wgsl,
+                as_name: Some("\"shaders/vordie_jfa_functions.wgsl\"".into()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        Some(Self {
+            device,
+            queue,
+            composer,
+            sampler,
+        })
+    }
+
+    fn shader(
+        &mut self,
+        source: &str,
+        file_path: &str,
+        shader_defs: &[&str],
+    ) -> wgpu::ShaderModule {
+        let module = self
+            .composer
+            .make_naga_module(NagaModuleDescriptor {
+                source,
+                file_path,
+                shader_defs: shader_defs
+                    .iter()
+                    .map(|def| (def.to_string(), ShaderDefValue::Bool(true)))
+                    .collect::<HashMap<_, _>>()
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            })
+            .unwrap();
+        self.device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(file_path),
+                source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+            })
+    }
+
+    fn texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::Texture {
+        self.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    }
+
+    fn uniform<T: encase::ShaderType + encase::internal::WriteInto>(
+        &self,
+        value: &T,
+    ) -> wgpu::Buffer {
+        let mut bytes = encase::UniformBuffer::new(Vec::new());
+        bytes.write(value).unwrap();
+        let bytes = bytes.into_inner();
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: bytes.len() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.queue.write_buffer(&buffer, 0, &bytes);
+        buffer
+    }
+}
+
+/// One pass of either backend. The layout is built explicitly like the plugin does, since the
+/// derived one would want filterable textures and only holds the bindings a shader uses.
+struct Stage {
+    pipeline: Pipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bindings: &'static [Binding],
+}
+
+/// What each binding of a stage holds, in binding order.
+#[derive(Clone, Copy)]
+enum Binding {
+    /// The input texture at this index.
+    Source(usize),
+    Sampler,
+    Settings,
+    Params,
+    Destination,
+}
+
+/// The compute entry points share one layout, the inverse source is the second input.
+const COMPUTE_BINDINGS: &[Binding] = &[
+    Binding::Source(0),
+    Binding::Sampler,
+    Binding::Settings,
+    Binding::Params,
+    Binding::Destination,
+    Binding::Source(1),
+];
+
+enum Pipeline {
+    Fragment(wgpu::RenderPipeline),
+    Compute(wgpu::ComputePipeline),
+}
+
+impl Stage {
+    fn layout(
+        gpu: &Gpu,
+        precision: VordieJfaPrecision,
+        bindings: &[Binding],
+    ) -> (wgpu::BindGroupLayout, wgpu::PipelineLayout) {
+        let entries: Vec<_> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| wgpu::BindGroupLayoutEntry {
+                binding: index as u32,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: match binding {
+                    Binding::Source(_) => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    Binding::Sampler => {
+                        wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering)
+                    }
+                    Binding::Settings | Binding::Params => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    Binding::Destination => wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: precision.texture_format(),
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &entries,
+                });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        (bind_group_layout, pipeline_layout)
+    }
+
+    fn fragment(
+        gpu: &mut Gpu,
+        precision: VordieJfaPrecision,
+        source: &str,
+        file_path: &str,
+        shader_defs: &[&str],
+        bindings: &'static [Binding],
+    ) -> Self {
+        let vertex = gpu.shader(FULLSCREEN_SHADER, "fullscreen.wgsl", &[]);
+        let fragment = gpu.shader(source, file_path, shader_defs);
+        let (bind_group_layout, layout) = Self::layout(gpu, precision, bindings);
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(file_path),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &vertex,
+                    entry_point: "fullscreen_vertex_shader",
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &fragment,
+                    entry_point: "fragment",
+                    compilation_options: Default::default(),
+                    targets: &[Some(precision.texture_format().into())],
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+            });
+        Self {
+            pipeline: Pipeline::Fragment(pipeline),
+            bind_group_layout,
+            bindings,
+        }
+    }
+
+    fn compute(gpu: &mut Gpu, precision: VordieJfaPrecision, entry_point: &str) -> Self {
+        let shader_defs: &[&str] = match precision {
+            VordieJfaPrecision::Half => &[],
+            VordieJfaPrecision::Full => &["JFA_FULL_PRECISION"],
+        };
+        let module = gpu.shader(
+            include_str!("../assets/shaders/vordie_jfa_compute.wgsl"),
+            "vordie_jfa_compute.wgsl",
+            shader_defs,
+        );
+        let (bind_group_layout, layout) = Self::layout(gpu, precision, COMPUTE_BINDINGS);
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                module: &module,
+                entry_point,
+                compilation_options: Default::default(),
+            });
+        Self {
+            pipeline: Pipeline::Compute(pipeline),
+            bind_group_layout,
+            bindings: COMPUTE_BINDINGS,
+        }
+    }
+
+    /// Seed, inverse seed, jump flood and distance field stages of `backend`.
+    fn all(gpu: &mut Gpu, backend: VordieJfaBackend, precision: VordieJfaPrecision) -> [Self; 4] {
+        use Binding::*;
+
+        match backend {
+            VordieJfaBackend::Fragment => [
+                Self::fragment(
+                    gpu,
+                    precision,
+                    include_str!("../assets/shaders/vordie_seed.wgsl"),
+                    "vordie_seed.wgsl",
+                    &[],
+                    &[Source(0), Sampler, Settings],
+                ),
+                Self::fragment(
+                    gpu,
+                    precision,
+                    include_str!("../assets/shaders/vordie_seed.wgsl"),
+                    "vordie_seed.wgsl",
+                    &["INVERT_MASK"],
+                    &[Source(0), Sampler, Settings],
+                ),
+                Self::fragment(
+                    gpu,
+                    precision,
+                    include_str!("../assets/shaders/vordie_jfa.wgsl"),
+                    "vordie_jfa.wgsl",
+                    &[],
+                    &[Source(0), Sampler, Settings, Params],
+                ),
+                Self::fragment(
+                    gpu,
+                    precision,
+                    include_str!("../assets/shaders/vordie_dis_field.wgsl"),
+                    "vordie_dis_field.wgsl",
+                    &[],
+                    &[Source(0), Sampler, Settings, Source(1)],
+                ),
+            ],
+            VordieJfaBackend::Compute => [
+                Self::compute(gpu, precision, "seed"),
+                Self::compute(gpu, precision, "inverse_seed"),
+                Self::compute(gpu, precision, "jump_flood"),
+                Self::compute(gpu, precision, "distance_field"),
+            ],
+        }
+    }
+
+    fn run(
+        &self,
+        gpu: &Gpu,
+        sources: &[&wgpu::Texture],
+        destination: &wgpu::Texture,
+        settings: &wgpu::Buffer,
+        params: Params,
+    ) {
+        let params = gpu.uniform(&params);
+        let source_views: Vec<_> = sources
+            .iter()
+            .map(|source| source.create_view(&Default::default()))
+            .collect();
+        let destination_view = destination.create_view(&Default::default());
+        let entries: Vec<_> = self
+            .bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| wgpu::BindGroupEntry {
+                binding: index as u32,
+                resource: match binding {
+                    Binding::Source(source) => {
+                        wgpu::BindingResource::TextureView(&source_views[*source])
+                    }
+                    Binding::Sampler => wgpu::BindingResource::Sampler(&gpu.sampler),
+                    Binding::Settings => settings.as_entire_binding(),
+                    Binding::Params => params.as_entire_binding(),
+                    Binding::Destination => wgpu::BindingResource::TextureView(&destination_view),
+                },
+            })
+            .collect();
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        });
+
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        match &self.pipeline {
+            Pipeline::Fragment(pipeline) => {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &destination_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations::default(),
+                    })],
+                    ..Default::default()
+                });
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            Pipeline::Compute(pipeline) => {
+                let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.set_bind_group(0, &bind_group, &[]);
+                compute_pass.dispatch_workgroups(
+                    destination.width().div_ceil(8),
+                    destination.height().div_ceil(8),
+                    1,
+                );
+            }
+        }
+        gpu.queue.submit([encoder.finish()]);
+    }
+}
+
+/// Seeds, jump floods and builds the signed distance field of `mask` the way the nodes do.
+/// Returns the voronoi and distance field textures as rgba.
+fn distance_field(
+    gpu: &mut Gpu,
+    backend: VordieJfaBackend,
+    precision: VordieJfaPrecision,
+    mask: &[bool],
+    width: u32,
+    height: u32,
+) -> (Vec<[f32; 4]>, Vec<[f32; 4]>) {
+    let attachment = match backend {
+        VordieJfaBackend::Fragment => wgpu::TextureUsages::RENDER_ATTACHMENT,
+        VordieJfaBackend::Compute => wgpu::TextureUsages::STORAGE_BINDING,
+    };
+    let usage = attachment | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC;
+    let scene = gpu.texture(
+        width,
+        height,
+        wgpu::TextureFormat::Rgba16Float,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    );
+    let [seed, voronoi, inverse_seed, inverse_voronoi, ping_pong, distance] =
+        [(); 6].map(|_| gpu.texture(width, height, precision.texture_format(), usage));
+
+    // Surfaces only need alpha, 1.0 is 0x3c00 as an f16.
+    let pixels: Vec<u8> = mask
+        .iter()
+        .flat_map(|&surface| [0, 0, 0, if surface { 0x3c00u16 } else { 0 }])
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    gpu.queue.write_texture(
+        scene.as_image_copy(),
+        &pixels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 8),
+            rows_per_image: None,
+        },
+        scene.size(),
+    );
+
+    let settings = gpu.uniform(&VordieLightSettings::default());
+    let screen_pixel_size = Vec2::new(width as f32, height as f32);
+    let [seed_stage, inverse_seed_stage, jfa_stage, dis_field_stage] =
+        Stage::all(gpu, backend, precision);
+
+    seed_stage.run(gpu, &[&scene, &scene], &seed, &settings, Params::default());
+    inverse_seed_stage.run(
+        gpu,
+        &[&scene, &scene],
+        &inverse_seed,
+        &settings,
+        Params::default(),
+    );
+    let offsets = jfa_offsets(screen_pixel_size);
+    for (seed, voronoi) in [(&seed, &voronoi), (&inverse_seed, &inverse_voronoi)] {
+        let mut prev = seed;
+        for (i, offset) in offsets.iter().copied().enumerate() {
+            let destination = if (offsets.len() - 1 - i).is_multiple_of(2) {
+                voronoi
+            } else {
+                &ping_pong
+            };
+            let params = Params {
+                screen_pixel_size,
+                offset,
+            };
+            jfa_stage.run(gpu, &[prev, prev], destination, &settings, params);
+            prev = destination;
+        }
+    }
+    dis_field_stage.run(
+        gpu,
+        &[&voronoi, &inverse_voronoi],
+        &distance,
+        &settings,
+        Params::default(),
+    );
+
+    (read_back(gpu, &voronoi), read_back(gpu, &distance))
+}
+
+fn read_back(gpu: &Gpu, texture: &wgpu::Texture) -> Vec<[f32; 4]> {
+    let (width, height) = (texture.width(), texture.height());
+    let pixel_size = texture.format().block_copy_size(None).unwrap();
+    let padded_row = (width * pixel_size).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (padded_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = gpu.device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    gpu.queue.submit([encoder.finish()]);
+
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    gpu.device.poll(wgpu::Maintain::Wait);
+    let bytes = buffer.slice(..).get_mapped_range();
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (y * padded_row + x * pixel_size) as usize))
+        .map(|offset| {
+            [0, 1, 2, 3].map(|channel| match texture.format() {
+                wgpu::TextureFormat::Rgba32Float => {
+                    let at = offset + channel * 4;
+                    f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+                }
+                _ => {
+                    let at = offset + channel * 2;
+                    f16_to_f32(u16::from_le_bytes([bytes[at], bytes[at + 1]]))
+                }
+            })
+        })
+        .collect()
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f => f32::INFINITY,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// A few discs and a line, so the jump flood has seeds of different sizes to propagate.
+fn test_mask(width: u32, height: u32) -> Vec<bool> {
+    let discs = [(0.25, 0.3, 0.08), (0.7, 0.65, 0.12), (0.8, 0.15, 0.03)];
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let uv = Vec2::new(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            let in_disc = discs
+                .iter()
+                .any(|&(cx, cy, r)| uv.distance(Vec2::new(cx, cy)) < r);
+            in_disc || (y == height * 3 / 4 && x < width / 3)
+        })
+        .collect()
+}
+
+#[test]
+fn compute_backend_matches_fragment_backend() {
+    let Some(mut gpu) = Gpu::new() else {
+        eprintln!("no adapter with compute shaders, skipping");
+        return;
+    };
+
+    let (width, height) = (64, 64);
+    let mask = test_mask(width, height);
+    let [(fragment_voronoi, fragment_distance), (compute_voronoi, compute_distance)] =
+        [VordieJfaBackend::Fragment, VordieJfaBackend::Compute].map(|backend| {
+            distance_field(
+                &mut gpu,
+                backend,
+                VordieJfaPrecision::Half,
+                &mask,
+                width,
+                height,
+            )
+        });
+
+    for (i, (fragment, compute)) in fragment_voronoi
+        .iter()
+        .zip(&compute_voronoi)
+        .chain(fragment_distance.iter().zip(&compute_distance))
+        .enumerate()
+    {
+        assert!(
+            fragment
+                .iter()
+                .zip(compute)
+                .all(|(a, b)| (a - b).abs() < 1e-3),
+            "pixel {i}: fragment {fragment:?}, compute {compute:?}"
+        );
+    }
+    // Make sure the comparison isn't between two empty textures.
+    assert!(fragment_distance.iter().any(|pixel| pixel[0] > 0.1));
+}
+
+/// Signed distance from every pixel center to the closest pixel center on the other side of the
+/// surface edge, in the aspect-corrected UV the shaders use. Clamped to 1.0 like the shaders.
+fn brute_force_distance_field(mask: &[bool], width: u32, height: u32) -> Vec<f32> {
+    let longest = width.max(height) as f32;
+    let pixels: Vec<(Vec2, bool)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Vec2::new(x as f32, y as f32)))
+        .zip(mask.iter().copied())
+        .collect();
+    pixels
+        .iter()
+        .map(|&(pixel, surface)| {
+            let closest = pixels
+                .iter()
+                .filter(|(_, other)| *other != surface)
+                .map(|(other, _)| other.distance(pixel) / longest)
+                .fold(1.0, f32::min);
+            if surface {
+                -closest
+            } else {
+                closest
+            }
+        })
+        .collect()
+}
+
+/// Checks both backends against the brute force distance field of `mask`.
+fn assert_distance_field_matches(
+    precision: VordieJfaPrecision,
+    mask: &[bool],
+    width: u32,
+    height: u32,
+    tolerance: f32,
+) {
+    let Some(mut gpu) = Gpu::new() else {
+        eprintln!("no adapter with compute shaders, skipping");
+        return;
+    };
+
+    let expected = brute_force_distance_field(mask, width, height);
+    for backend in [VordieJfaBackend::Fragment, VordieJfaBackend::Compute] {
+        let (_, distance) = distance_field(&mut gpu, backend, precision, mask, width, height);
+        for (i, (pixel, expected)) in distance.iter().zip(&expected).enumerate() {
+            assert!(
+                (pixel[0] - expected).abs() < tolerance,
+                "{backend:?} {width}x{height}, pixel ({}, {}): got {}, expected {expected}",
+                i as u32 % width,
+                i as u32 / width,
+                pixel[0],
+            );
+        }
+    }
+}
+
+/// The jump flood can miss the closest seed by about a pixel, f16 adds a little more.
+fn half_precision_tolerance(width: u32, height: u32) -> f32 {
+    1.5 / width.max(height) as f32 + 2e-3
+}
+
+fn assert_test_mask_distance_field_matches(width: u32, height: u32) {
+    assert_distance_field_matches(
+        VordieJfaPrecision::Half,
+        &test_mask(width, height),
+        width,
+        height,
+        half_precision_tolerance(width, height),
+    );
+}
+
+#[test]
+fn distance_field_on_wide_target() {
+    assert_test_mask_distance_field_matches(96, 40);
+}
+
+#[test]
+fn distance_field_on_tall_target() {
+    assert_test_mask_distance_field_matches(40, 96);
+}
+
+#[test]
+fn distance_field_on_odd_sized_target() {
+    assert_test_mask_distance_field_matches(67, 45);
+}
+
+#[test]
+fn seeds_on_the_first_row_and_column() {
+    let (width, height) = (48, 32);
+    let mask: Vec<bool> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x == 0 && y > height / 2) || (y == 0 && x == 30)))
+        .collect();
+    assert_distance_field_matches(
+        VordieJfaPrecision::Half,
+        &mask,
+        width,
+        height,
+        half_precision_tolerance(width, height),
+    );
+}
+
+#[test]
+fn empty_scene_has_no_seeds() {
+    let (width, height) = (24, 16);
+    assert_distance_field_matches(
+        VordieJfaPrecision::Half,
+        &vec![false; (width * height) as usize],
+        width,
+        height,
+        1e-3,
+    );
+}
+
+#[test]
+fn covered_scene_is_inside_everywhere() {
+    let (width, height) = (24, 16);
+    assert_distance_field_matches(
+        VordieJfaPrecision::Half,
+        &vec![true; (width * height) as usize],
+        width,
+        height,
+        1e-3,
+    );
+}
+
+#[test]
+fn distance_field_is_negative_inside_surfaces() {
+    // A block thick enough to have an interior, with a hole in it.
+    let (width, height) = (48, 40);
+    let mask: Vec<bool> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| (8..40).contains(&x) && (6..34).contains(&y) && !(x == 20 && y == 20))
+        .collect();
+    assert_distance_field_matches(
+        VordieJfaPrecision::Half,
+        &mask,
+        width,
+        height,
+        half_precision_tolerance(width, height),
+    );
+}
+
+#[test]
+fn full_precision_is_exact_on_large_targets() {
+    // Wider than f16 can address to the pixel, with a seed at each end.
+    let (width, height) = (4100, 3);
+    let mask: Vec<bool> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y) == (0, 0) || (x, y) == (width - 1, 2)))
+        .collect();
+    assert_distance_field_matches(VordieJfaPrecision::Full, &mask, width, height, 1e-5);
+}
+
+/// Uploads rgba pixels into a texture the passes can read.
+fn upload(gpu: &Gpu, pixels: &[[f32; 4]], width: u32, height: u32) -> wgpu::Texture {
+    let texture = gpu.texture(
+        width,
+        height,
+        wgpu::TextureFormat::Rgba32Float,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    );
+    let bytes: Vec<u8> = pixels
+        .iter()
+        .flatten()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    gpu.queue.write_texture(
+        texture.as_image_copy(),
+        &bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 16),
+            rows_per_image: None,
+        },
+        texture.size(),
+    );
+    texture
+}
+
+#[test]
+fn atrous_pass_does_not_blur_through_walls() {
+    let Some(mut gpu) = Gpu::new() else {
+        eprintln!("no adapter with compute shaders, skipping");
+        return;
+    };
+    // A wall one pixel wide, with light on its left only.
+    let (width, height) = (32, 16);
+    let mask: Vec<bool> = (0..height)
+        .flat_map(|_| (0..width).map(|x| x == 16))
+        .collect();
+    let (_, distance) = distance_field(
+        &mut gpu,
+        VordieJfaBackend::Fragment,
+        VordieJfaPrecision::Full,
+        &mask,
+        width,
+        height,
+    );
+    let light: Vec<[f32; 4]> = (0..height)
+        .flat_map(|_| (0..width).map(|x| [if x < 16 { 1.0 } else { 0.0 }; 4]))
+        .collect();
+    // Noisy enough everywhere that the luminance difference alone doesn't stop the filter.
+    let moments = vec![[0.0, 0.0, 1.0, 1.0]; mask.len()];
+    let scene: Vec<[f32; 4]> = mask
+        .iter()
+        .map(|&surface| [0.0, 0.0, 0.0, if surface { 1.0 } else { 0.0 }])
+        .collect();
+    let sources =
+        [&light, &moments, &scene, &distance].map(|pixels| upload(&gpu, pixels, width, height));
+
+    use Binding::*;
+    let stage = Stage::fragment(
+        &mut gpu,
+        VordieJfaPrecision::Full,
+        include_str!("../assets/shaders/vordie_atrous.wgsl"),
+        "vordie_atrous.wgsl",
+        &[],
+        &[Source(0), Source(1), Source(2), Source(3), Settings, Params],
+    );
+    let destination = gpu.texture(
+        width,
+        height,
+        wgpu::TextureFormat::Rgba32Float,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    );
+    let settings = gpu.uniform(&VordieLightSettings::default());
+    for step in [1.0, 2.0, 4.0, 8.0] {
+        stage.run(
+            &gpu,
+            &sources.each_ref(),
+            &destination,
+            &settings,
+            crate::Params {
+                screen_pixel_size: Vec2::new(width as f32, height as f32),
+                offset: step,
+            },
+        );
+        for (i, pixel) in read_back(&gpu, &destination).iter().enumerate() {
+            let x = i as u32 % width;
+            if x < 16 {
+                assert!(pixel[0] > 0.999, "light lost at {x} with a step of {step}");
+            } else if x > 16 {
+                assert_eq!(pixel[0], 0.0, "light leaked to {x} with a step of {step}");
+            }
+        }
+    }
+}
+
+#[test]
+fn radiance_cascades_reach_across_the_view() {
+    // The last interval ends 2730 pixels out, past the 1101 pixel diagonal.
+    assert_eq!(radiance_cascade_count(UVec2::new(960, 540)), 6);
+    // Cascade 2's probes are as wide as the view, its interval ends 42 pixels out.
+    assert_eq!(radiance_cascade_count(UVec2::new(8, 8)), 3);
+    // Too thin for the probes of cascade 1.
+    assert_eq!(radiance_cascade_count(UVec2::new(4100, 3)), 1);
+}
+
+#[test]
+fn bounces_are_capped_when_extracted() {
+    let settings = VordieLightSettings {
+        u_max_bounces: u32::MAX,
+        ..Default::default()
+    };
+    let extracted = VordieLightSettings::extract_component(&settings).unwrap();
+    assert_eq!(extracted.u_max_bounces, VordieLightSettings::MAX_BOUNCES);
+
+    // Everything else is left as it is.
+    let settings = VordieLightSettings {
+        u_max_bounces: 2,
+        ..Default::default()
+    };
+    let extracted = VordieLightSettings::extract_component(&settings).unwrap();
+    assert_eq!(extracted.u_max_bounces, 2);
+    assert_eq!(extracted.u_rays_per_pixel, settings.u_rays_per_pixel);
+}
+
+#[test]
+fn denoise_iterations_are_capped_when_extracted() {
+    let settings = VordieLightSettings {
+        u_denoise_iterations: 100,
+        ..Default::default()
+    };
+    let extracted = VordieLightSettings::extract_component(&settings).unwrap();
+    assert_eq!(
+        extracted.u_denoise_iterations,
+        VordieLightSettings::MAX_DENOISE_ITERATIONS
+    );
+
+    let extracted = VordieLightSettings::extract_component(&Default::default()).unwrap();
+    assert_eq!(extracted.u_denoise_iterations, 3);
+}
+
+#[test]
+fn sky_colors_are_linear_gradients() {
+    assert_eq!(VordieSkyParams::from(None).mode, 0);
+
+    // A single color is a gradient that doesn't change.
+    let sky = VordieSkyParams::from(Some(&VordieSky::Color(Color::srgb(0.5, 0.5, 0.5))));
+    assert_eq!(sky.mode, 1);
+    assert_eq!(sky.up, sky.down);
+    assert!((sky.up.x - 0.214).abs() < 1e-3);
+
+    let sky = VordieSkyParams::from(Some(&VordieSky::Gradient {
+        up: Color::WHITE,
+        down: Color::BLACK,
+    }));
+    assert_eq!((sky.mode, sky.up.x, sky.down.x), (1, 1.0, 0.0));
+
+    let sky = VordieSkyParams::from(Some(&VordieSky::Texture(Handle::default())));
+    assert_eq!(sky.mode, 2);
+}
+
+#[test]
+fn point_lights_keep_the_emitter_on_their_entity() {
+    let mut main_world = MainWorld::default();
+    main_world.init_resource::<Assets<ColorMaterial>>();
+    main_world.init_resource::<Assets<TextureAtlasLayout>>();
+    let mut visible = ViewVisibility::HIDDEN;
+    visible.set();
+    let entity = main_world
+        .spawn((
+            Mesh2dHandle::default(),
+            GlobalTransform::default(),
+            visible,
+            VordieEmitter::default(),
+            VordiePointLight::default(),
+        ))
+        .id();
+
+    let mut world = World::new();
+    world.insert_resource(main_world);
+    world.init_resource::<ExtractedVordieScene>();
+    world.run_system_once(clear_vordie_scene);
+    world.run_system_once(extract_vordie_scene);
+    world.run_system_once(extract_vordie_point_lights);
+
+    let scene = world.resource::<ExtractedVordieScene>();
+    assert!(scene.items.contains_key(&entity));
+    assert!(scene.point_lights.contains_key(&entity));
+    assert_eq!(scene.iter().count(), 2);
+}
+
+fn extracted_view(translation: Vec3, size: UVec2) -> ExtractedView {
+    let half_size = size.as_vec2() / 2.0;
+    ExtractedView {
+        clip_from_view: Mat4::orthographic_rh(
+            -half_size.x,
+            half_size.x,
+            -half_size.y,
+            half_size.y,
+            0.0,
+            1000.0,
+        ),
+        world_from_view: GlobalTransform::from_translation(translation),
+        clip_from_world: None,
+        hdr: false,
+        viewport: UVec4::new(0, 0, size.x, size.y),
+        color_grading: Default::default(),
+    }
+}
+
+fn extracted_camera(target_size: UVec2) -> ExtractedCamera {
+    ExtractedCamera {
+        target: None,
+        physical_viewport_size: Some(target_size),
+        physical_target_size: Some(target_size),
+        viewport: None,
+        render_graph: Core2d.intern(),
+        order: 0,
+        output_mode: CameraOutputMode::default(),
+        msaa_writeback: false,
+        clear_color: Default::default(),
+        sorted_camera_index_for_target: 0,
+        exposure: 0.0,
+        hdr: false,
+    }
+}
+
+fn clip_from_world(view: &ExtractedView) -> Mat4 {
+    view.clip_from_view * view.world_from_view.compute_matrix().inverse()
+}
+
+/// Runs `prepare_previous_views` like a new frame, where the render world starts out without the
+/// components it inserted last time.
+fn next_frame(world: &mut World, camera: Entity, view: ExtractedView) -> (Mat4, bool) {
+    world.entity_mut(camera).remove::<VordieResetHistory>();
+    world.entity_mut(camera).insert(view);
+    world.run_system_once(prepare_previous_views);
+    let previous = world.get::<VordiePreviousView>(camera).unwrap();
+    (
+        previous.clip_from_world,
+        world.get::<VordieResetHistory>(camera).is_some(),
+    )
+}
+
+#[test]
+fn previous_view_lags_one_frame() {
+    let mut world = World::new();
+    world.init_resource::<VordiePreviousViews>();
+    let size = UVec2::new(960, 540);
+    let start = extracted_view(Vec3::ZERO, size);
+    let start_clip_from_world = clip_from_world(&start);
+    let camera = world
+        .spawn((VordieLightSettings::default(), extracted_camera(size)))
+        .id();
+
+    // Nothing to reproject from on the first frame, and the new history is cleared.
+    assert_eq!(
+        next_frame(&mut world, camera, start),
+        (start_clip_from_world, true)
+    );
+
+    // After the camera pans the history is still in the starting view.
+    let panned = extracted_view(Vec3::new(64.0, -32.0, 0.0), size);
+    assert_eq!(
+        next_frame(&mut world, camera, panned),
+        (start_clip_from_world, false)
+    );
+
+    // Cameras without the light pass are ignored and removed ones forgotten.
+    let other = world.spawn(extracted_view(Vec3::ZERO, size)).id();
+    world.entity_mut(camera).despawn();
+    world.run_system_once(prepare_previous_views);
+    assert!(world.get::<VordiePreviousView>(other).is_none());
+    assert!(world.resource::<VordiePreviousViews>().0.is_empty());
+}
+
+#[test]
+fn history_resets_on_cuts_and_resizes() {
+    let mut world = World::new();
+    world.init_resource::<VordiePreviousViews>();
+    let size = UVec2::new(960, 540);
+    let camera = world
+        .spawn((VordieLightSettings::default(), extracted_camera(size)))
+        .id();
+    next_frame(&mut world, camera, extracted_view(Vec3::ZERO, size));
+
+    // Teleporting further than the screen is wide.
+    let teleported = extracted_view(Vec3::new(2000.0, 0.0, 0.0), size);
+    let teleported_clip_from_world = clip_from_world(&teleported);
+    assert_eq!(
+        next_frame(&mut world, camera, teleported),
+        (teleported_clip_from_world, true)
+    );
+
+    // Resizing the window.
+    world
+        .entity_mut(camera)
+        .insert(extracted_camera(UVec2::new(1280, 720)));
+    let resized = extracted_view(Vec3::new(2000.0, 0.0, 0.0), UVec2::new(1280, 720));
+    let resized_clip_from_world = clip_from_world(&resized);
+    assert_eq!(
+        next_frame(&mut world, camera, resized),
+        (resized_clip_from_world, true)
+    );
+
+    // Asking for a reset, with the camera barely moving.
+    let moved = extracted_view(Vec3::new(2001.0, 0.0, 0.0), UVec2::new(1280, 720));
+    let moved_clip_from_world = clip_from_world(&moved);
+    world.entity_mut(camera).insert(moved);
+    world.entity_mut(camera).insert(VordieResetHistory);
+    world.run_system_once(prepare_previous_views);
+    let previous = world.get::<VordiePreviousView>(camera).unwrap();
+    assert_eq!(previous.clip_from_world, moved_clip_from_world);
+
+    // And nothing on the frame after.
+    let still = extracted_view(Vec3::new(2001.0, 0.0, 0.0), UVec2::new(1280, 720));
+    assert_eq!(
+        next_frame(&mut world, camera, still),
+        (moved_clip_from_world, false)
+    );
+}
+
+#[test]
+fn history_follows_the_lighting_textures_size() {
+    let mut world = World::new();
+    world.init_resource::<VordiePreviousViews>();
+    let target_size = UVec2::new(960, 540);
+    let camera = world
+        .spawn((
+            VordieLightSettings::default(),
+            VordieResolutionScale::Quarter,
+            extracted_camera(target_size),
+        ))
+        .id();
+    next_frame(&mut world, camera, extracted_view(Vec3::ZERO, target_size));
+
+    // The lighting textures are sized from the whole target, a smaller viewport keeps them.
+    let viewport_size = UVec2::new(480, 540);
+    assert!(
+        !next_frame(
+            &mut world,
+            camera,
+            extracted_view(Vec3::ZERO, viewport_size)
+        )
+        .1
+    );
+    let history = world.resource::<VordiePreviousViews>().0[&camera].size;
+    assert_eq!(history, VordieResolutionScale::Quarter.apply(target_size));
+
+    // Resizing the target with the viewport unchanged still starts a new history.
+    world
+        .entity_mut(camera)
+        .insert(extracted_camera(UVec2::new(1280, 720)));
+    assert!(
+        next_frame(
+            &mut world,
+            camera,
+            extracted_view(Vec3::ZERO, viewport_size)
+        )
+        .1
+    );
+}